@@ -0,0 +1,72 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares a serial MCTS search with root and tree parallelization over the same number of
+//! rounds, on an empty 7x7 board with four in a row. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+use tic_tac_toe::{
+  game::{Game, Rule, Variant},
+  player::{Mcts, MctsConfig, Parallelization, Player},
+};
+
+const ROUNDS: u32 = 20_000;
+const THREADS: usize = 4;
+const SAMPLES: u64 = 5;
+
+/// Returns the mean time of a first move searched with `threads` threads.
+fn measure(threads: usize, parallelization: Parallelization) -> Duration {
+  let game = Game::new(Variant::new(7, 7, 4, Rule::Freestyle).unwrap());
+  let config = MctsConfig::new(
+    ROUNDS,
+    MctsConfig::EXPLORATION_DEFAULT,
+    None,
+    MctsConfig::NODES_CAPACITY_DEFAULT,
+  )
+  .with_threads(threads, parallelization);
+  let mut total = Duration::ZERO;
+
+  for seed in 0..SAMPLES {
+    let mut mcts = Mcts::new(config.clone());
+    mcts.new_game(seed);
+    let start = Instant::now();
+    mcts.get_move(&game, None).unwrap();
+    total += start.elapsed();
+  }
+
+  total / SAMPLES as u32
+}
+
+fn main() {
+  let serial = measure(1, Parallelization::Root);
+  println!(
+    "serial: {:>8.1} ms for {} rounds",
+    serial.as_secs_f64() * 1000.0,
+    ROUNDS
+  );
+
+  for (name, parallelization) in [
+    ("root", Parallelization::Root),
+    ("tree", Parallelization::Tree),
+  ] {
+    let time = measure(THREADS, parallelization);
+    println!(
+      "{}:   {:>8.1} ms on {} threads, {:.2}x the speed of the serial search",
+      name,
+      time.as_secs_f64() * 1000.0,
+      THREADS,
+      serial.as_secs_f64() / time.as_secs_f64()
+    );
+  }
+}
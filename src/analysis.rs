@@ -0,0 +1,336 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+  game::{Game, GameError, Square},
+  player::{Evaluation, Mcts, MctsConfig, Minimax, Player, PlayerError},
+};
+use serde::Serialize;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AnalysisError {
+  #[error(transparent)]
+  Game(#[from] GameError),
+  #[error(transparent)]
+  Player(#[from] PlayerError),
+  #[error(transparent)]
+  Json(#[from] serde_json::Error),
+}
+
+/// Minimax searches the whole game tree, so it only runs on positions with at most this many
+/// empty squares.
+const MINIMAX_EMPTY_SQUARES_MAXIMUM: usize = 16;
+
+/// Result of a move with perfect play, seen from the side making it.
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum Value {
+  Win,
+  Draw,
+  Loss,
+}
+
+impl std::fmt::Display for Value {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Self::Win => write!(f, "win"),
+      Self::Draw => write!(f, "draw"),
+      Self::Loss => write!(f, "loss"),
+    }
+  }
+}
+
+/// Analysis of a move, seen from the side making it.
+#[derive(Serialize)]
+struct Move {
+  square: String,
+  /// Value with perfect play, when minimax has been run.
+  value: Option<Value>,
+  /// Marks placed until the game ends with perfect play, including the move itself.
+  plies: Option<u8>,
+  /// Share of the MCTS playouts made through the move.
+  visit_share: f64,
+  /// Mean MCTS result of the move, a draw counting as half a win, when it has been searched.
+  win_rate: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Analysis {
+  position: String,
+  side: String,
+  moves: Vec<Move>,
+}
+
+impl Move {
+  fn get_value_label(&self) -> String {
+    match (self.value, self.plies) {
+      (Some(Value::Win), Some(plies)) => format!("W{}", plies),
+      (Some(Value::Loss), Some(plies)) => format!("L{}", plies),
+      (Some(_), _) => "D".to_string(),
+      (None, _) => "?".to_string(),
+    }
+  }
+
+  fn get_value_description(&self) -> String {
+    match (self.value, self.plies) {
+      (Some(value @ (Value::Win | Value::Loss)), Some(plies)) => {
+        format!("{} in {}", value, plies)
+      }
+      (Some(value), _) => value.to_string(),
+      (None, _) => "-".to_string(),
+    }
+  }
+}
+
+/// Prints the minimax value of every move of the side to move in `position`, together with the
+/// share of the playouts an MCTS search made through it and its win rate, either as grids and a
+/// table or as JSON.
+pub(super) fn analyze(
+  position: &Game,
+  mcts_config: MctsConfig,
+  seed: u64,
+  json: bool,
+) -> Result<(), AnalysisError> {
+  if let Some(outcome) = position.get_outcome() {
+    return Err(GameError::GameIsOver(outcome.clone()).into());
+  }
+
+  let squares = position.get_empty_squares();
+
+  let evaluations = if squares.len() <= MINIMAX_EMPTY_SQUARES_MAXIMUM {
+    Minimax::new()
+      .evaluate(position)
+      .map_err(PlayerError::from)?
+  } else {
+    Vec::new()
+  };
+
+  let mut mcts = Mcts::new(mcts_config);
+  mcts.new_game(seed);
+  let statistics = mcts.analyze(position).map_err(PlayerError::from)?;
+  let playouts: u32 = statistics.iter().map(|(_, playouts, _)| playouts).sum();
+
+  let moves = squares
+    .iter()
+    .map(|square| {
+      let evaluation = evaluations
+        .iter()
+        .find(|(other, _)| other == square)
+        .map(|(_, evaluation)| *evaluation);
+      let searched = statistics.iter().find(|(other, _, _)| other == square);
+
+      Move {
+        square: square.to_string(),
+        value: evaluation.map(|evaluation| match evaluation {
+          Evaluation::Win(_) => Value::Win,
+          Evaluation::Draw => Value::Draw,
+          Evaluation::Loss(_) => Value::Loss,
+        }),
+        plies: match evaluation {
+          Some(Evaluation::Win(plies) | Evaluation::Loss(plies)) => Some(plies),
+          _ => None,
+        },
+        visit_share: searched.map_or(0.0, |(_, visits, _)| {
+          f64::from(*visits) / f64::from(playouts.max(1))
+        }),
+        win_rate: searched.map(|(_, _, mean)| *mean),
+      }
+    })
+    .collect();
+
+  let analysis = Analysis {
+    position: position.to_string(),
+    side: position.get_side_to_move().to_string(),
+    moves,
+  };
+
+  if json {
+    println!("{}", serde_json::to_string_pretty(&analysis)?);
+  } else {
+    print(position, &analysis);
+  }
+
+  Ok(())
+}
+
+/// Describes the value of `position` for the side to move: the minimax value when the position
+/// has few enough empty squares, otherwise the win rate of the move an MCTS search prefers.
+pub(super) fn describe_position(
+  position: &Game,
+  minimax: &mut Minimax,
+  mcts: &mut Mcts,
+) -> Result<String, AnalysisError> {
+  if let Some(outcome) = position.get_outcome() {
+    return Ok(outcome.to_string());
+  }
+
+  let side = position.get_side_to_move();
+
+  if position.get_empty_squares().len() <= MINIMAX_EMPTY_SQUARES_MAXIMUM {
+    let evaluations = minimax.evaluate(position).map_err(PlayerError::from)?;
+    let best = evaluations
+      .iter()
+      .rev()
+      .max_by_key(|(_, evaluation)| evaluation.get_rank());
+
+    return Ok(match best {
+      Some((square, Evaluation::Win(plies))) => {
+        format!("{} wins in {} marks with {}", side, plies, square)
+      }
+      Some((square, Evaluation::Loss(plies))) => {
+        format!("{} loses in {} marks, at best with {}", side, plies, square)
+      }
+      Some((square, Evaluation::Draw)) => format!("draw, for example with {}", square),
+      None => "draw".to_string(),
+    });
+  }
+
+  let statistics = mcts.analyze(position).map_err(PlayerError::from)?;
+
+  Ok(
+    match statistics.iter().max_by_key(|(_, playouts, _)| *playouts) {
+      Some((square, _, mean)) => format!(
+        "{} scores {:.1}% with {} by mcts",
+        side,
+        mean * 100.0,
+        square
+      ),
+      None => "unknown".to_string(),
+    },
+  )
+}
+
+fn print(position: &Game, analysis: &Analysis) {
+  let find = |square: &Square| {
+    let square = square.to_string();
+    analysis.moves.iter().find(|other| other.square == square)
+  };
+
+  println!(
+    "position: {}\nside to move: {}\n",
+    analysis.position, analysis.side
+  );
+  print_side_by_side(&[
+    ("board", position.format_grid(|_| None)),
+    (
+      "minimax",
+      position.format_grid(|square| find(square).map(Move::get_value_label)),
+    ),
+    (
+      "mcts visits %",
+      position.format_grid(|square| {
+        find(square).map(|entry| format!("{:.0}", entry.visit_share * 100.0))
+      }),
+    ),
+  ]);
+
+  println!("\nmove  minimax     visits  win rate");
+
+  for entry in &analysis.moves {
+    println!(
+      "{:<5} {:<11} {:>5.1}%  {}",
+      entry.square,
+      entry.get_value_description(),
+      entry.visit_share * 100.0,
+      match entry.win_rate {
+        Some(win_rate) => format!("{:>7.1}%", win_rate * 100.0),
+        None => format!("{:>8}", "-"),
+      }
+    );
+  }
+}
+
+/// Prints grids next to each other, each under its title.
+fn print_side_by_side(grids: &[(&str, String)]) {
+  const GAP: &str = "    ";
+  let widths: Vec<usize> = grids
+    .iter()
+    .map(|(title, grid)| {
+      grid
+        .lines()
+        .map(str::len)
+        .chain([title.len()])
+        .max()
+        .unwrap_or(0)
+    })
+    .collect();
+  let height = grids
+    .iter()
+    .map(|(_, grid)| grid.lines().count())
+    .max()
+    .unwrap_or(0);
+  let mut lines = vec![grids
+    .iter()
+    .zip(&widths)
+    .map(|((title, _), width)| format!("{:<width$}", title))
+    .collect::<Vec<_>>()];
+
+  for index in 0..height {
+    lines.push(
+      grids
+        .iter()
+        .zip(&widths)
+        .map(|((_, grid), width)| format!("{:<width$}", grid.lines().nth(index).unwrap_or("")))
+        .collect(),
+    );
+  }
+
+  for line in lines {
+    println!("{}", line.join(GAP).trim_end());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Move, Value};
+
+  fn get_move(value: Option<Value>, plies: Option<u8>) -> Move {
+    Move {
+      square: "b2".to_string(),
+      value,
+      plies,
+      visit_share: 0.0,
+      win_rate: None,
+    }
+  }
+
+  #[test]
+  fn values_are_labelled_with_the_marks_until_the_end() {
+    let win = get_move(Some(Value::Win), Some(3));
+    assert_eq!(win.get_value_label(), "W3");
+    assert_eq!(win.get_value_description(), "win in 3");
+
+    let loss = get_move(Some(Value::Loss), Some(2));
+    assert_eq!(loss.get_value_label(), "L2");
+    assert_eq!(loss.get_value_description(), "loss in 2");
+
+    let draw = get_move(Some(Value::Draw), None);
+    assert_eq!(draw.get_value_label(), "D");
+    assert_eq!(draw.get_value_description(), "draw");
+
+    let unknown = get_move(None, None);
+    assert_eq!(unknown.get_value_label(), "?");
+    assert_eq!(unknown.get_value_description(), "-");
+  }
+
+  #[test]
+  fn values_are_written_in_lowercase_in_json() {
+    let json = serde_json::to_string(&get_move(Some(Value::Loss), Some(2))).unwrap();
+    assert_eq!(
+      json,
+      r#"{"square":"b2","value":"loss","plies":2,"visit_share":0.0,"win_rate":null}"#
+    );
+  }
+}
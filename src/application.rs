@@ -13,10 +13,29 @@
 // limitations under the License.
 
 use super::{
-  controller::{Controller, ControllerError, GameCount},
-  player::PlayerType,
+  analysis::{self, AnalysisError},
+  controller::{
+    Console, Controller, ControllerError, GameCount, Logger, OutputFormat, Recorder, TimeControl,
+  },
+  engine::{self, EngineError},
+  game::{Game, GameError, Rule, Variant},
+  player::{
+    Context, MctsConfig, MctsError, Parallelization, PlayerError, PlayerSpec, Remote, RemoteError,
+  },
+  record::{self, RecordError},
+  solver::{self, SolverError, Table},
+  sprt::{self, Bounds, SprtError},
+  tournament::{self, TournamentError},
+};
+use clap::{
+  error::ErrorKind, parser::ValueSource, Args, CommandFactory, FromArgMatches, Parser, Subcommand,
+};
+use std::{
+  io::{self, BufReader},
+  net::TcpListener,
+  path::PathBuf,
+  time::Duration,
 };
-use clap::Parser;
 use thiserror::Error;
 
 #[derive(Parser)]
@@ -26,13 +45,18 @@ use thiserror::Error;
   long_about = "This command-line application allows you to play the classic game of Tic-tac-toe against a friend or an AI opponent"
 )]
 struct Arguments {
-  /// Sets the player for 'x'
-  #[arg(short = 'x', long, value_enum, default_value_t=PlayerType::PLAYER_X_DEFAULT)]
-  player_x: PlayerType,
+  #[command(subcommand)]
+  command: Option<Command>,
 
-  /// Sets the player for 'o'
-  #[arg(short = 'o', long, value_enum, default_value_t=PlayerType::PLAYER_O_DEFAULT)]
-  player_o: PlayerType,
+  /// Sets the player for 'x', called player 1: alpha-beta, external, mcts, minimax, perfect,
+  /// random, remote or user, optionally followed by options for 'mcts', 'external' and 'remote'
+  /// players, such as 'mcts:rounds=1000,c=1.0' or 'external:command=tic-tac-toe engine mcts'
+  #[arg(short = 'x', long, value_name = "PLAYER", default_value = "user")]
+  player_x: PlayerSpec,
+
+  /// Sets the player for 'o', called player 2, written like the player for 'x'
+  #[arg(short = 'o', long, value_name = "PLAYER", default_value = "mcts")]
+  player_o: PlayerSpec,
 
   /// Sets the number of games to be played
   #[arg(
@@ -43,21 +67,561 @@ struct Arguments {
       value_parser = clap::value_parser!(GameCount).range(Controller::MINIMUM_GAMES_COUNT as i64..)
   )]
   game_count: GameCount,
+
+  /// Makes the players swap 'x' and 'o' after every game, so that neither moves first more often
+  #[arg(long)]
+  alternate: bool,
+
+  /// Sets how the games and the results are printed
+  #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+  output_format: OutputFormat,
+
+  /// Appends every finished game to the given file as a record, which the replay command steps
+  /// through
+  #[arg(long, value_name = "PATH")]
+  record: Option<PathBuf>,
+
+  /// Waits for a player to join at the given address, such as 127.0.0.1:7878, which then plays
+  /// in place of the player for 'o'
+  #[arg(long, value_name = "ADDRESS", conflicts_with = "join")]
+  host: Option<String>,
+
+  /// Joins the game hosted at the given address with the player for 'x', which plays whichever
+  /// side the host gives it
+  #[arg(long, value_name = "ADDRESS")]
+  join: Option<String>,
+
+  #[command(flatten)]
+  position: PositionArguments,
+
+  #[command(flatten)]
+  table: TableArguments,
+
+  #[command(flatten)]
+  time: TimeArguments,
+
+  /// Sets the number of search rounds per move of 'mcts' players
+  #[arg(
+      long,
+      global = true,
+      value_name = "ROUNDS",
+      default_value_t = MctsConfig::ROUNDS_DEFAULT,
+      value_parser = clap::value_parser!(u32).range(1..)
+  )]
+  mcts_rounds: u32,
+
+  /// Sets the exploration constant of 'mcts' players
+  #[arg(
+      long,
+      global = true,
+      value_name = "C",
+      default_value_t = MctsConfig::EXPLORATION_DEFAULT,
+      value_parser = parse_non_negative
+  )]
+  mcts_c: f64,
+
+  /// Makes 'mcts' players search for the given time per move instead of a fixed number of rounds
+  #[arg(
+    long,
+    global = true,
+    value_name = "MILLISECONDS",
+    conflicts_with = "mcts_rounds"
+  )]
+  mcts_time_ms: Option<u64>,
+
+  /// Sets the number of tree nodes 'mcts' players reserve room for up front, when that much
+  /// memory is available
+  #[arg(
+      long,
+      global = true,
+      value_name = "NODES",
+      default_value_t = MctsConfig::NODES_CAPACITY_DEFAULT,
+      value_parser = parse_capacity
+  )]
+  mcts_capacity: usize,
+
+  /// Sets the number of threads 'mcts' players search with
+  #[arg(
+      long,
+      global = true,
+      value_name = "THREADS",
+      default_value_t = MctsConfig::THREADS_DEFAULT,
+      value_parser = clap::value_parser!(u16).range(1..=MctsConfig::THREADS_MAXIMUM as i64)
+  )]
+  threads: u16,
+
+  /// Sets how 'mcts' players split the search between threads
+  #[arg(long, global = true, value_enum, default_value_t = Parallelization::Root)]
+  mcts_parallelization: Parallelization,
+
+  /// Makes 'mcts' players blend all-moves-as-first statistics into the search, weighing them
+  /// equally with their own statistics after the given number of playouts
+  #[arg(long, global = true, value_name = "PLAYOUTS", value_parser = parse_non_negative)]
+  mcts_rave: Option<f64>,
+
+  /// Makes 'mcts' players only consider empty squares within the given number of steps of a
+  /// mark, which keeps the search usable on large boards such as Gomoku
+  #[arg(long, global = true, value_name = "DISTANCE", value_parser = clap::value_parser!(u8).range(1..))]
+  mcts_neighborhood: Option<u8>,
+
+  /// Seeds the random choices of all players, making every game reproducible unless 'mcts'
+  /// players search with a time limit or share a tree between threads
+  #[arg(long, global = true, value_name = "SEED")]
+  seed: Option<u64>,
+}
+
+/// Arguments choosing the board and the rule of the game.
+#[derive(Args)]
+struct VariantArguments {
+  /// Sets the number of columns of the board
+  #[arg(
+      long,
+      default_value_t = Variant::TIC_TAC_TOE.get_width(),
+      value_parser = clap::value_parser!(u8).range(1..=Variant::MAXIMUM_WIDTH as i64)
+  )]
+  width: u8,
+
+  /// Sets the number of rows of the board
+  #[arg(
+      long,
+      default_value_t = Variant::TIC_TAC_TOE.get_height(),
+      value_parser = clap::value_parser!(u8).range(1..)
+  )]
+  height: u8,
+
+  /// Sets the number of marks in a row needed to win
+  #[arg(
+      short,
+      long,
+      default_value_t = Variant::TIC_TAC_TOE.get_k(),
+      value_parser = clap::value_parser!(u8).range(1..)
+  )]
+  k: u8,
+
+  /// Sets which rows of k marks win
+  #[arg(long, value_enum, default_value_t = Rule::Freestyle)]
+  rule: Rule,
+
+  /// Plays Gomoku, five in a row on a 15x15 board, instead of the given board size
+  #[arg(long, conflicts_with_all = ["width", "height", "k"])]
+  gomoku: bool,
 }
 
+/// Arguments choosing the position games start from.
+#[derive(Args)]
+struct PositionArguments {
+  #[command(flatten)]
+  variant: VariantArguments,
+
+  /// Starts every game from the given position, written as the rows from the top separated by
+  /// '/', with x, o and _ for an empty square, followed by the side to move and optionally by k
+  /// and the rule, such as 'xo_/_x_/__o x'
+  #[arg(long, conflicts_with_all = ["width", "height", "k", "rule", "gomoku"])]
+  position: Option<Game>,
+}
+
+/// Arguments of the commands creating players.
+#[derive(Args)]
+struct TableArguments {
+  /// Makes 'perfect' players play from a table written by the solve command instead of solving
+  /// the game when it starts
+  #[arg(long, value_name = "PATH")]
+  table: Option<PathBuf>,
+}
+
+/// Arguments of the commands playing games between players.
+#[derive(Args)]
+struct TimeArguments {
+  /// Gives every player the given time for all its moves in a game, losing the game when it
+  /// runs out
+  #[arg(long, value_name = "MILLISECONDS")]
+  time_ms: Option<u64>,
+
+  /// Adds the given time to the clock of a player after each of its moves
+  #[arg(long, value_name = "MILLISECONDS", default_value_t = 0)]
+  increment_ms: u64,
+
+  /// Makes a player that takes longer than the given time for a move lose the game
+  #[arg(long, value_name = "MILLISECONDS")]
+  move_time_ms: Option<u64>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Prints the minimax value of every move from a position, with the distance to the end of
+  /// the game, next to the share of the playouts and the win rate an MCTS search gives it
+  Analyze {
+    /// The position to analyze, such as 'xo_/_x_/__o x'
+    position: Game,
+
+    /// Prints the analysis as JSON
+    #[arg(long)]
+    json: bool,
+  },
+  /// Steps through the games of a record file written with --record, printing the board and the
+  /// evaluation of the engines after every move
+  Replay {
+    /// The record file
+    path: PathBuf,
+
+    /// Replays only the game with the given number, counting from 1
+    #[arg(long, value_name = "NUMBER")]
+    game: Option<usize>,
+  },
+  /// Serves a player to another program over a line-based text protocol on the standard input
+  /// and output, with commands such as 'position', 'go movetime 500' and 'quit'
+  Engine {
+    /// The player served, written like the players of a tournament
+    player: PlayerSpec,
+
+    #[command(flatten)]
+    table: TableArguments,
+  },
+  /// Solves every position reachable from the empty board, which has to have at most 16
+  /// squares, and prints the value and the best moves of the empty board
+  Solve {
+    /// Writes the solved table to the given file, for 'perfect' players to load with --table
+    #[arg(long, value_name = "PATH")]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    variant: VariantArguments,
+  },
+  /// Plays every pair of players against each other, swapping sides after every game, and
+  /// prints a crosstable with the scores and the Elo ratings of the players
+  Tournament {
+    /// The players, each a player type optionally followed by options for 'mcts' players, such
+    /// as 'mcts:rounds=1000,c=1.0'
+    #[arg(required = true, num_args = 2..)]
+    players: Vec<PlayerSpec>,
+
+    /// Sets the number of games every pair of players plays
+    #[arg(
+        long,
+        value_name = "GAMES",
+        default_value_t = 10,
+        value_parser = clap::value_parser!(GameCount).range(1..)
+    )]
+    games: GameCount,
+
+    #[command(flatten)]
+    position: PositionArguments,
+
+    #[command(flatten)]
+    table: TableArguments,
+
+    #[command(flatten)]
+    time: TimeArguments,
+  },
+  /// Plays a player against a baseline, swapping sides after every game, until a sequential
+  /// probability ratio test decides whether the player is stronger, printing the log-likelihood
+  /// ratio after every game
+  Sprt {
+    /// The player tested, written like the players of a tournament
+    player: PlayerSpec,
+
+    /// The player it is compared with
+    baseline: PlayerSpec,
+
+    /// Sets the Elo difference of the null hypothesis
+    #[arg(long, default_value_t = 0.0)]
+    elo0: f64,
+
+    /// Sets the Elo difference of the alternative hypothesis
+    #[arg(long, default_value_t = 10.0)]
+    elo1: f64,
+
+    /// Sets the probability of accepting the alternative hypothesis when the null one holds
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    /// Sets the probability of accepting the null hypothesis when the alternative one holds
+    #[arg(long, default_value_t = 0.05)]
+    beta: f64,
+
+    /// Stops the test without a decision after the given number of games
+    #[arg(
+        long,
+        value_name = "GAMES",
+        default_value_t = GameCount::MAX,
+        value_parser = clap::value_parser!(GameCount).range(1..)
+    )]
+    maximum_games: GameCount,
+
+    #[command(flatten)]
+    position: PositionArguments,
+
+    #[command(flatten)]
+    table: TableArguments,
+
+    #[command(flatten)]
+    time: TimeArguments,
+  },
+}
+
+impl Arguments {
+  /// Parses the command line, rejecting the arguments of games played without a command, such as
+  /// the board size, when a command is given instead of ignoring them.
+  fn parse_command_line() -> Self {
+    let mut command = Self::command();
+    let matches = command.get_matches_mut();
+
+    if let Some((name, _)) = matches.subcommand() {
+      let given = command.get_arguments().find(|argument| {
+        !argument.is_global_set()
+          && matches.value_source(argument.get_id().as_str()) == Some(ValueSource::CommandLine)
+      });
+
+      if let Some(argument) = given {
+        let flag = match argument.get_long() {
+          Some(long) => format!("--{}", long),
+          None => format!("-{}", argument.get_short().unwrap_or_default()),
+        };
+        command
+          .error(
+            ErrorKind::ArgumentConflict,
+            format!("the subcommand '{}' cannot be used with '{}'", name, flag),
+          )
+          .exit();
+      }
+    }
+
+    Self::from_arg_matches(&matches).unwrap_or_else(|error| error.exit())
+  }
+
+  fn get_mcts_config(&self) -> MctsConfig {
+    MctsConfig::new(
+      self.mcts_rounds,
+      self.mcts_c,
+      self.mcts_time_ms.map(Duration::from_millis),
+      self.mcts_capacity,
+    )
+    .with_threads(self.threads.into(), self.mcts_parallelization)
+    .with_rave(self.mcts_rave)
+    .with_neighborhood(self.mcts_neighborhood)
+  }
+
+  /// Returns the context players are created with, loading the table of `table` if given.
+  fn get_context(&self, table: &TableArguments) -> Result<Context, SolverError> {
+    let table = match &table.table {
+      Some(path) => Some(Table::load(path)?),
+      None => None,
+    };
+
+    Ok(Context::new(self.get_mcts_config(), table).with_seeded(self.seed.is_some()))
+  }
+}
+
+impl VariantArguments {
+  fn get_variant(&self) -> Result<Variant, GameError> {
+    if self.gomoku {
+      let gomoku = Variant::GOMOKU;
+      Variant::new(
+        gomoku.get_width(),
+        gomoku.get_height(),
+        gomoku.get_k(),
+        self.rule,
+      )
+    } else {
+      Variant::new(self.width, self.height, self.k, self.rule)
+    }
+  }
+}
+
+impl PositionArguments {
+  /// Returns the position every game starts from, which must not be over yet.
+  fn get_position(&self) -> Result<Game, GameError> {
+    let position = match &self.position {
+      Some(position) => position.clone(),
+      None => Game::new(self.variant.get_variant()?),
+    };
+
+    match position.get_outcome() {
+      Some(outcome) => Err(GameError::GameIsOver(outcome.clone())),
+      None => Ok(position),
+    }
+  }
+}
+
+impl TimeArguments {
+  fn get_time_control(&self) -> Option<TimeControl> {
+    TimeControl::new(
+      self.time_ms.map(Duration::from_millis),
+      Duration::from_millis(self.increment_ms),
+      self.move_time_ms.map(Duration::from_millis),
+    )
+  }
+}
+
+/// Parses a finite number that is not negative, like the options of 'mcts' players.
+fn parse_non_negative(text: &str) -> Result<f64, String> {
+  match text.parse::<f64>() {
+    Ok(value) if value.is_finite() && value >= 0.0 => Ok(value),
+    Ok(_) => Err("a finite number of at least 0 is required".to_string()),
+    Err(error) => Err(error.to_string()),
+  }
+}
+
+/// Parses a number of tree nodes within the range of the 'capacity' option of 'mcts' players.
+fn parse_capacity(text: &str) -> Result<usize, String> {
+  match text.parse::<usize>() {
+    Ok(value) if value <= MctsConfig::NODES_CAPACITY_MAXIMUM => Ok(value),
+    Ok(_) => Err(format!(
+      "at most {} nodes are allowed",
+      MctsConfig::NODES_CAPACITY_MAXIMUM
+    )),
+    Err(error) => Err(error.to_string()),
+  }
+}
+
+/// Errors of the command-line application.
 #[derive(Error, Debug)]
-pub(super) enum ApplicationError {
+pub enum ApplicationError {
+  /// Analysing a position failed.
+  #[error(transparent)]
+  Analysis(#[from] AnalysisError),
+  /// Playing a match failed.
   #[error(transparent)]
   Controller(#[from] ControllerError),
+  /// Serving a player as an engine failed.
+  #[error(transparent)]
+  Engine(#[from] EngineError),
+  /// The variant, position or a move given is not valid.
+  #[error(transparent)]
+  Game(#[from] GameError),
+  /// A player could not be created or failed to move.
+  #[error(transparent)]
+  Player(#[from] PlayerError),
+  /// Reading or replaying a record failed.
+  #[error(transparent)]
+  Record(#[from] RecordError),
+  /// Solving, saving or loading a table failed.
+  #[error(transparent)]
+  Solver(#[from] SolverError),
+  /// Running a sequential probability ratio test failed.
+  #[error(transparent)]
+  Sprt(#[from] SprtError),
+  /// Running a tournament failed.
+  #[error(transparent)]
+  Tournament(#[from] TournamentError),
 }
 
 pub(super) fn main() -> Result<(), ApplicationError> {
-  let arguments = Arguments::parse();
-  Controller::new(
-    arguments.player_x.get_player(),
-    arguments.player_o.get_player(),
+  let arguments = Arguments::parse_command_line();
+  let mcts_config = arguments.get_mcts_config();
+  let seed = arguments.seed.unwrap_or_else(rand::random);
+
+  if arguments.seed.is_some() && !mcts_config.is_reproducible() {
+    return Err(PlayerError::from(MctsError::NotReproducible).into());
+  }
+
+  match &arguments.command {
+    Some(Command::Analyze { position, json }) => {
+      analysis::analyze(position, mcts_config, seed, *json)?;
+      return Ok(());
+    }
+    Some(Command::Replay { path, game }) => {
+      record::replay(path, *game, mcts_config, seed)?;
+      return Ok(());
+    }
+    Some(Command::Engine { player, table }) => {
+      if player.is_interactive() {
+        return Err(EngineError::InteractivePlayer(player.to_string()).into());
+      }
+
+      engine::serve(
+        player.get_player(&arguments.get_context(table)?)?,
+        BufReader::new(io::stdin()),
+        io::stdout(),
+      )?;
+      return Ok(());
+    }
+    Some(Command::Solve { output, variant }) => {
+      solver::solve(variant.get_variant()?, output.as_deref())?;
+      return Ok(());
+    }
+    Some(Command::Tournament {
+      players,
+      games,
+      position,
+      table,
+      time,
+    }) => {
+      tournament::play(
+        players,
+        *games,
+        &position.get_position()?,
+        time.get_time_control(),
+        &arguments.get_context(table)?,
+        seed,
+      )?;
+      return Ok(());
+    }
+    Some(Command::Sprt {
+      player,
+      baseline,
+      elo0,
+      elo1,
+      alpha,
+      beta,
+      maximum_games,
+      position,
+      table,
+      time,
+    }) => {
+      let bounds = Bounds::new(*elo0, *elo1, *alpha, *beta)?;
+      sprt::test(
+        [player, baseline],
+        &bounds,
+        *maximum_games,
+        &position.get_position()?,
+        time.get_time_control(),
+        &arguments.get_context(table)?,
+        seed,
+      )?;
+      return Ok(());
+    }
+    None => (),
+  }
+
+  let context = arguments.get_context(&arguments.table)?;
+
+  if let Some(address) = &arguments.join {
+    println!("joining the game at {}", address);
+    engine::join(arguments.player_x.get_player(&context)?, address)?;
+    println!("the host has left");
+    return Ok(());
+  }
+
+  let position = arguments.position.get_position()?;
+  let player_o = match &arguments.host {
+    Some(address) => {
+      let remote_error = |error| PlayerError::from(RemoteError::Io(error));
+      let listener = TcpListener::bind(address).map_err(remote_error)?;
+      let address = listener.local_addr().map_err(remote_error)?;
+      println!("waiting for a player to join at {}", address);
+      Box::new(Remote::new(&listener)?)
+    }
+    None => arguments.player_o.get_player(&context)?,
+  };
+  let mut controller = Controller::new(
+    arguments.player_x.get_player(&context)?,
+    player_o,
     arguments.game_count,
+    arguments.alternate,
+    position,
+    seed,
   )
-  .engage()?;
+  .with_listener(match arguments.output_format {
+    OutputFormat::Text => Box::new(Console::new()),
+    format => Box::new(Logger::new(format)),
+  })
+  .with_time_control(arguments.time.get_time_control());
+
+  if let Some(path) = &arguments.record {
+    controller = controller.with_listener(Box::new(Recorder::new(path.clone())));
+  }
+
+  controller.play_match()?;
   Ok(())
 }
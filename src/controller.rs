@@ -12,105 +12,557 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Matches between two players and the listeners following them.
+
+mod console;
+mod output;
+mod recorder;
 use super::{
-  game::{Game, GameError, Outcome, Side},
-  player::{Player, PlayerError},
+  game::{Game, GameError, Outcome, Side, Square},
+  player::{self, Action, Clock, Player, PlayerError},
 };
+use std::time::{Duration, Instant};
 use thiserror::Error;
+pub use {
+  console::Console,
+  output::{Logger, OutputFormat},
+  recorder::Recorder,
+};
 
+/// Errors of the players, the games and the listeners of a match.
 #[derive(Error, Debug)]
-pub(super) enum ControllerError {
+pub enum ControllerError {
+  /// A player failed to move.
   #[error(transparent)]
   Player(#[from] PlayerError),
+  /// A move could not be placed, taken back or placed again.
   #[error(transparent)]
   Game(#[from] GameError),
+  /// A record could not be written as JSON.
+  #[error(transparent)]
+  Json(#[from] serde_json::Error),
+  /// Writing the output or a record file failed.
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+}
+
+/// Number of games of a match.
+pub type GameCount = u16;
+
+/// Results of one player across a match.
+#[derive(Default)]
+pub struct Results {
+  wins_as_x: GameCount,
+  wins_as_o: GameCount,
+  draws: GameCount,
+  losses_on_time: GameCount,
+}
+
+impl Results {
+  /// Returns the games won with either side.
+  pub fn get_wins(&self) -> GameCount {
+    self.wins_as_x + self.wins_as_o
+  }
+
+  /// Returns the games won playing x.
+  pub fn get_wins_as_x(&self) -> GameCount {
+    self.wins_as_x
+  }
+
+  /// Returns the games won playing o.
+  pub fn get_wins_as_o(&self) -> GameCount {
+    self.wins_as_o
+  }
+
+  /// Returns the games drawn.
+  pub fn get_draws(&self) -> GameCount {
+    self.draws
+  }
+
+  /// Returns the number of games the player lost because its time ran out.
+  pub fn get_losses_on_time(&self) -> GameCount {
+    self.losses_on_time
+  }
+}
+
+/// Time allowed to each player in a game: `time` on its clock at the start, unless it has all
+/// the time it needs, `increment` added to it after every move, as in Fischer's clock, and
+/// `move_limit` for any single move. A player that runs out of time loses.
+#[derive(Clone, Copy)]
+pub struct TimeControl {
+  time: Option<Duration>,
+  increment: Duration,
+  move_limit: Option<Duration>,
+}
+
+impl TimeControl {
+  /// Returns `None` when neither the time nor the time per move are limited.
+  pub fn new(
+    time: Option<Duration>,
+    increment: Duration,
+    move_limit: Option<Duration>,
+  ) -> Option<Self> {
+    if time.is_none() && move_limit.is_none() {
+      return None;
+    }
+
+    Some(Self {
+      time,
+      increment,
+      move_limit,
+    })
+  }
+
+  fn get_time(self) -> Duration {
+    self.time.unwrap_or(Duration::MAX)
+  }
+}
+
+/// Why a side lost a game before it was decided on the board.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Forfeit {
+  /// Its time ran out.
+  Time,
+  /// It gave the game up.
+  Resignation,
+  /// Its program chose a square that is taken or off the board.
+  IllegalMove,
+  /// Its engine reported an error or replied with something else than a move.
+  EngineFailure,
+  /// It left the game, which ends the match.
+  Disconnection,
+}
+
+impl std::fmt::Display for Forfeit {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Self::Time => write!(f, "time"),
+      Self::Resignation => write!(f, "resignation"),
+      Self::IllegalMove => write!(f, "illegal move"),
+      Self::EngineFailure => write!(f, "engine failure"),
+      Self::Disconnection => write!(f, "disconnection"),
+    }
+  }
 }
 
-pub(super) type GameCount = u16;
+/// How a game ended: the player of index `x` played x in game number `number` of the match, the
+/// game was played with `seed` and the side of `forfeit`, if any, lost it for the reason given
+/// there. `moves` holds every mark on the board in the order it was placed, with the time taken
+/// to choose it.
+pub struct Ending<'a> {
+  number: GameCount,
+  x: usize,
+  seed: u64,
+  moves: &'a [(Square, Duration)],
+  outcome: &'a Outcome,
+  forfeit: Option<(Side, Forfeit)>,
+}
+
+impl Ending<'_> {
+  /// Returns the number of the game in the match, counting from 1.
+  pub fn get_number(&self) -> GameCount {
+    self.number
+  }
+
+  /// Returns the index of the player that played x, in the order the players were given.
+  pub fn get_x(&self) -> usize {
+    self.x
+  }
+
+  /// Returns the seed the game was played with.
+  pub fn get_seed(&self) -> u64 {
+    self.seed
+  }
+
+  /// Returns every mark placed in the game, with the time taken to choose it.
+  pub fn get_moves(&self) -> &[(Square, Duration)] {
+    self.moves
+  }
+
+  /// Returns how the game ended.
+  pub fn get_outcome(&self) -> &Outcome {
+    self.outcome
+  }
+
+  /// Returns the side that forfeited the game and why, if it was not decided on the board.
+  pub fn get_forfeit(&self) -> Option<&(Side, Forfeit)> {
+    self.forfeit.as_ref()
+  }
+}
+
+/// Follows the games of a match, for example to print or record them. Players are given in the
+/// order they were passed to the controller, and games are numbered from 1.
+pub trait Listener {
+  /// Called before the first game of the match. The players swap sides after every game when
+  /// `alternate` is set.
+  fn notify_match_start(
+    &mut self,
+    _players: &[Box<dyn Player>; 2],
+    _position: &Game,
+    _alternate: bool,
+  ) -> Result<(), ControllerError> {
+    Ok(())
+  }
+
+  /// Called before game `number` starts from `game`, played with `seed` and with the player of
+  /// index `x` playing x.
+  fn notify_game_start(
+    &mut self,
+    _number: GameCount,
+    _seed: u64,
+    _x: usize,
+    _game: &Game,
+  ) -> Result<(), ControllerError> {
+    Ok(())
+  }
+
+  /// Called after a player has placed the mark on `square` in `elapsed`, leading to `game`.
+  fn notify_move(
+    &mut self,
+    _game: &Game,
+    _square: &Square,
+    _elapsed: Duration,
+  ) -> Result<(), ControllerError> {
+    Ok(())
+  }
+
+  /// Called after a user has taken back a move, leading to `game`.
+  fn notify_undo(&mut self, _game: &Game) -> Result<(), ControllerError> {
+    Ok(())
+  }
+
+  /// Called after a user has placed a move taken back again, leading to `game`.
+  fn notify_redo(&mut self, _game: &Game) -> Result<(), ControllerError> {
+    Ok(())
+  }
+
+  /// Called when a user has asked to take back a move in `game`, which has none to take back.
+  fn notify_undo_refused(&mut self, _game: &Game) -> Result<(), ControllerError> {
+    Ok(())
+  }
 
-pub(super) struct Controller {
-  player_x: Player,
-  player_o: Player,
+  /// Called when a user has asked to place a move again in `game`, which has none taken back.
+  fn notify_redo_refused(&mut self, _game: &Game) -> Result<(), ControllerError> {
+    Ok(())
+  }
+
+  /// Called once `game` has ended as told by `ending`.
+  fn notify_game_end(
+    &mut self,
+    _players: &[Box<dyn Player>; 2],
+    _position: &Game,
+    _game: &Game,
+    _ending: &Ending,
+  ) -> Result<(), ControllerError> {
+    Ok(())
+  }
+
+  /// Called after the last game of the match, with the `results` of `games` games.
+  fn notify_match_end(
+    &mut self,
+    _players: &[Box<dyn Player>; 2],
+    _results: &[Results; 2],
+    _games: GameCount,
+  ) -> Result<(), ControllerError> {
+    Ok(())
+  }
+}
+
+/// Plays a match of games between two players, counting the results of each.
+pub struct Controller {
+  players: [Box<dyn Player>; 2],
+  results: [Results; 2],
   game_count: GameCount,
-  x_win: GameCount,
-  o_win: GameCount,
-  draw: GameCount,
+  alternate: bool,
+  position: Game,
+  seed: u64,
+  listeners: Vec<Box<dyn Listener>>,
+  time_control: Option<TimeControl>,
+  games_played: GameCount,
+  /// Whether a player has left, after which no more games are played.
+  abandoned: bool,
 }
 
 impl Controller {
-  pub(super) const MINIMUM_GAMES_COUNT: GameCount = 1;
+  /// A match has at least one game.
+  pub const MINIMUM_GAMES_COUNT: GameCount = 1;
+  /// Taking back a move undoes the last mark of the side to move together with the reply to it.
+  const TAKE_BACK_MARKS: usize = 2;
 
-  pub(super) fn new(player_x: Player, player_o: Player, game_count: GameCount) -> Self {
+  /// Every game starts from `position`, with `player_x` playing x unless `alternate` is set, in
+  /// which case the players swap sides after every game. The first game is played with `seed`,
+  /// and every following game with a seed derived from the seed of the game before it. Running
+  /// a match with the seed printed at the start of a game therefore replays that game first.
+  /// Nothing is printed unless listeners are added.
+  pub fn new(
+    player_x: Box<dyn Player>,
+    player_o: Box<dyn Player>,
+    game_count: GameCount,
+    alternate: bool,
+    position: Game,
+    seed: u64,
+  ) -> Self {
     Self {
-      player_x,
-      player_o,
+      players: [player_x, player_o],
+      results: Default::default(),
       game_count,
-      x_win: 0,
-      o_win: 0,
-      draw: 0,
+      alternate,
+      position,
+      seed,
+      listeners: Vec::new(),
+      time_control: None,
+      games_played: 0,
+      abandoned: false,
     }
   }
 
-  pub(super) fn engage(mut self) -> Result<(), ControllerError> {
-    self.introduce_players();
-    self.play()?;
-    self.print_results();
-    Ok(())
+  /// Adds `listener` to the ones told about the match, which are told in the order they were
+  /// added.
+  pub fn with_listener(mut self, listener: Box<dyn Listener>) -> Self {
+    self.listeners.push(listener);
+    self
+  }
+
+  /// Limits the time of the players, who otherwise think as long as they like.
+  pub fn with_time_control(mut self, time_control: Option<TimeControl>) -> Self {
+    self.time_control = time_control;
+    self
+  }
+
+  /// Plays all the games, or the games until a player leaves, and returns the results of both
+  /// players, in the order they were given.
+  pub fn play_match(mut self) -> Result<[Results; 2], ControllerError> {
+    while self.play_next_game()? {}
+    Ok(self.results)
+  }
+
+  /// Plays the next game of the match, unless all of them have been played or a player has
+  /// left, and returns whether it did. The listeners are told about the end of the match after
+  /// the last game.
+  pub fn play_next_game(&mut self) -> Result<bool, ControllerError> {
+    if self.games_played >= self.game_count || self.abandoned {
+      return Ok(false);
+    }
+
+    if self.games_played == 0 {
+      for listener in &mut self.listeners {
+        listener.notify_match_start(&self.players, &self.position, self.alternate)?;
+      }
+    }
+
+    let x = if self.alternate {
+      self.games_played as usize % 2
+    } else {
+      0
+    };
+    self.play_one_game(x)?;
+    self.games_played += 1;
+
+    if self.games_played == self.game_count || self.abandoned {
+      for listener in &mut self.listeners {
+        listener.notify_match_end(&self.players, &self.results, self.games_played)?;
+      }
+    }
+
+    Ok(true)
+  }
+
+  /// Returns the results of both players so far, in the order they were given.
+  pub fn get_results(&self) -> &[Results; 2] {
+    &self.results
   }
 
-  fn play_one_game(&mut self) -> Result<(), ControllerError> {
-    println!("game start\n");
-    let mut game = Game::new();
-    game.print_grid();
+  /// Plays a game with the player of index `x` playing x.
+  fn play_one_game(&mut self, x: usize) -> Result<(), ControllerError> {
+    let seed = self.seed;
+    let mut game = self.position.clone();
+    let mut moves: Vec<(Square, Duration)> = Vec::new();
+    let mut clocks = [self
+      .time_control
+      .map_or(Duration::MAX, TimeControl::get_time); 2];
+
+    for (index, player) in self.players.iter_mut().enumerate() {
+      player.new_game(player::derive_seed(self.seed, index as u64 + 1));
+    }
+
+    self.seed = player::derive_seed(self.seed, 0);
+
+    for listener in &mut self.listeners {
+      listener.notify_game_start(self.games_played + 1, seed, x, &game)?;
+    }
 
     loop {
-      game.place_mark(&if *game.get_side_to_move() == Side::X {
-        self.player_x.get_move(&game)?
-      } else {
-        self.player_o.get_move(&game)?
-      })?;
-      game.print_grid();
+      let index = match game.get_side_to_move() {
+        Side::X => x,
+        Side::O => 1 - x,
+      };
+      let clock = self.time_control.map(|time_control| {
+        Clock::new(
+          clocks[index],
+          time_control.increment,
+          time_control.move_limit,
+        )
+      });
+      let start = Instant::now();
+      let action = match self.players[index].get_action(&game, clock.as_ref()) {
+        Err(error) if error.is_out_of_time() => Err(Forfeit::Time),
+        Err(error) if error.is_engine_failure() => Err(Forfeit::EngineFailure),
+        Err(error) if error.is_disconnected() => Err(Forfeit::Disconnection),
+        action => Ok(action?),
+      };
+      let elapsed = start.elapsed();
 
-      if let Some(outcome) = game.get_outcome() {
-        match outcome {
-          Outcome::Draw => {
-            println!("draw");
-            self.draw += 1;
+      let action = match (action, &clock) {
+        (Ok(action), Some(clock)) if elapsed <= clock.get_deadline() => {
+          clocks[index] = clocks[index].saturating_sub(elapsed);
+          action
+        }
+        (Ok(action), None) => action,
+        (Ok(_), Some(_)) => return self.forfeit_game(&game, x, seed, &moves, Forfeit::Time),
+        (Err(forfeit), _) => return self.forfeit_game(&game, x, seed, &moves, forfeit),
+      };
+
+      match action {
+        Action::Mark(square) => {
+          match game.place_mark(&square) {
+            Err(_) if self.players[index].is_external() => {
+              return self.forfeit_game(&game, x, seed, &moves, Forfeit::IllegalMove);
+            }
+            result => result?,
+          }
+
+          moves.push((square, elapsed));
+          self.notify_players(&square);
+
+          for listener in &mut self.listeners {
+            listener.notify_move(&game, &square, elapsed)?;
+          }
+
+          if let Some(time_control) = &self.time_control {
+            clocks[index] = clocks[index].saturating_add(time_control.increment);
           }
-          Outcome::XWin => {
-            println!("x win");
-            self.x_win += 1;
+        }
+        Action::Undo if game.get_history().len() >= Self::TAKE_BACK_MARKS => {
+          for _ in 0..Self::TAKE_BACK_MARKS {
+            game.undo_mark()?;
+            moves.pop();
+
+            for player in &mut self.players {
+              player.notify_undo();
+            }
+          }
+
+          for listener in &mut self.listeners {
+            listener.notify_undo(&game)?;
+          }
+        }
+        Action::Redo if game.get_undone().len() >= Self::TAKE_BACK_MARKS => {
+          for _ in 0..Self::TAKE_BACK_MARKS {
+            let square = game.redo_mark()?;
+            moves.push((square, Duration::ZERO));
+            self.notify_players(&square);
           }
-          Outcome::OWin => {
-            println!("o win");
-            self.o_win += 1;
+
+          for listener in &mut self.listeners {
+            listener.notify_redo(&game)?;
+          }
+        }
+        Action::Resign => {
+          return self.forfeit_game(&game, x, seed, &moves, Forfeit::Resignation);
+        }
+        Action::Undo => {
+          for listener in &mut self.listeners {
+            listener.notify_undo_refused(&game)?;
+          }
+
+          continue;
+        }
+        Action::Redo => {
+          for listener in &mut self.listeners {
+            listener.notify_redo_refused(&game)?;
           }
-        };
 
-        return Ok(());
+          continue;
+        }
+      }
+
+      if let Some(outcome) = game.get_outcome() {
+        return self.finish_game(
+          &game,
+          Ending {
+            number: self.games_played + 1,
+            x,
+            seed,
+            moves: &moves,
+            outcome,
+            forfeit: None,
+          },
+        );
       }
     }
   }
 
-  fn play(&mut self) -> Result<(), ControllerError> {
-    for _ in 0..self.game_count {
-      self.play_one_game()?;
-    }
+  /// Ends `game`, played with `seed` and with the player of index `x` playing x, with a loss of
+  /// the side to move for the reason `forfeit`.
+  fn forfeit_game(
+    &mut self,
+    game: &Game,
+    x: usize,
+    seed: u64,
+    moves: &[(Square, Duration)],
+    forfeit: Forfeit,
+  ) -> Result<(), ControllerError> {
+    let side = game.get_side_to_move().clone();
+    self.abandoned = forfeit == Forfeit::Disconnection;
+    let outcome = match side {
+      Side::X => Outcome::OWin,
+      Side::O => Outcome::XWin,
+    };
 
-    Ok(())
+    self.finish_game(
+      game,
+      Ending {
+        number: self.games_played + 1,
+        x,
+        seed,
+        moves,
+        outcome: &outcome,
+        forfeit: Some((side, forfeit)),
+      },
+    )
   }
 
-  fn introduce_players(&self) {
-    println!(
-      "\nplayer x: {}\nplayer o: {}\n",
-      self.player_x, self.player_o
-    )
+  /// Tells the listeners about the end of `game` and counts its result.
+  fn finish_game(&mut self, game: &Game, ending: Ending) -> Result<(), ControllerError> {
+    for listener in &mut self.listeners {
+      listener.notify_game_end(&self.players, &self.position, game, &ending)?;
+    }
+
+    let x = ending.x;
+
+    match ending.outcome {
+      Outcome::Draw => {
+        self.results[x].draws += 1;
+        self.results[1 - x].draws += 1;
+      }
+      Outcome::XWin => self.results[x].wins_as_x += 1,
+      Outcome::OWin => self.results[1 - x].wins_as_o += 1,
+    };
+
+    match ending.forfeit {
+      Some((Side::X, Forfeit::Time)) => self.results[x].losses_on_time += 1,
+      Some((Side::O, Forfeit::Time)) => self.results[1 - x].losses_on_time += 1,
+      _ => (),
+    }
+
+    Ok(())
   }
 
-  fn print_results(self) {
-    println!(
-      "\nResults:\nx win: {}\no win: {}\ndraw:  {}\n",
-      self.x_win, self.o_win, self.draw
-    );
+  /// Informs both players about a mark placed by either side, which lets them keep their search
+  /// trees or think on the time of the opponent.
+  fn notify_players(&mut self, square: &Square) {
+    for player in &mut self.players {
+      player.notify_move(square);
+    }
   }
 }
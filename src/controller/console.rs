@@ -0,0 +1,163 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{ControllerError, Ending, Forfeit, GameCount, Listener, Results};
+use crate::{
+  game::{Game, Square},
+  player::Player,
+};
+use std::time::Duration;
+
+/// Prints the players before the match, the board after every move and the results at the end,
+/// for people to follow.
+#[derive(Default)]
+pub struct Console;
+
+impl Console {
+  /// Creates a console listener.
+  pub fn new() -> Self {
+    Self
+  }
+}
+
+impl Listener for Console {
+  fn notify_match_start(
+    &mut self,
+    players: &[Box<dyn Player>; 2],
+    position: &Game,
+    alternate: bool,
+  ) -> Result<(), ControllerError> {
+    println!("\nvariant: {}", position.get_variant());
+
+    if position.get_marks_count() > 0 {
+      println!("position: {}", position);
+    }
+
+    for (index, player) in players.iter().enumerate() {
+      println!("player {}: {}", index + 1, player);
+    }
+
+    if alternate {
+      println!("players swap x and o after every game, player 1 starts with x");
+    } else {
+      println!("player 1 plays x and player 2 plays o");
+    }
+
+    println!();
+    Ok(())
+  }
+
+  fn notify_game_start(
+    &mut self,
+    _number: GameCount,
+    seed: u64,
+    x: usize,
+    game: &Game,
+  ) -> Result<(), ControllerError> {
+    println!("game start (seed: {}, x: player {})\n", seed, x + 1);
+    game.print_grid();
+    Ok(())
+  }
+
+  fn notify_move(
+    &mut self,
+    game: &Game,
+    _square: &Square,
+    _elapsed: Duration,
+  ) -> Result<(), ControllerError> {
+    game.print_grid();
+    Ok(())
+  }
+
+  fn notify_undo(&mut self, game: &Game) -> Result<(), ControllerError> {
+    game.print_grid();
+    Ok(())
+  }
+
+  fn notify_redo(&mut self, game: &Game) -> Result<(), ControllerError> {
+    game.print_grid();
+    Ok(())
+  }
+
+  fn notify_undo_refused(&mut self, _game: &Game) -> Result<(), ControllerError> {
+    println!("there is no move to take back");
+    Ok(())
+  }
+
+  fn notify_redo_refused(&mut self, _game: &Game) -> Result<(), ControllerError> {
+    println!("there is no move to place again");
+    Ok(())
+  }
+
+  fn notify_game_end(
+    &mut self,
+    _players: &[Box<dyn Player>; 2],
+    _position: &Game,
+    _game: &Game,
+    ending: &Ending,
+  ) -> Result<(), ControllerError> {
+    match &ending.forfeit {
+      Some((side, Forfeit::Time)) => println!("{} ({} lost on time)", ending.outcome, side),
+      Some((side, Forfeit::Resignation)) => println!("{} ({} resigned)", ending.outcome, side),
+      Some((side, Forfeit::IllegalMove)) => {
+        println!("{} ({} made an illegal move)", ending.outcome, side)
+      }
+      Some((side, Forfeit::EngineFailure)) => {
+        println!("{} (the engine of {} failed)", ending.outcome, side)
+      }
+      Some((side, Forfeit::Disconnection)) => {
+        println!("{} ({} left the game)", ending.outcome, side)
+      }
+      None => println!("{}", ending.outcome),
+    }
+
+    Ok(())
+  }
+
+  fn notify_match_end(
+    &mut self,
+    players: &[Box<dyn Player>; 2],
+    results: &[Results; 2],
+    _games: GameCount,
+  ) -> Result<(), ControllerError> {
+    println!("\nResults:\n          wins as x  wins as o  draws  losses on time");
+
+    for (index, results) in results.iter().enumerate() {
+      println!(
+        "player {}  {:>9}  {:>9}  {:>5}  {:>14}",
+        index + 1,
+        results.wins_as_x,
+        results.wins_as_o,
+        results.draws,
+        results.losses_on_time
+      );
+    }
+
+    println!();
+
+    for (index, player) in players.iter().enumerate() {
+      if let Some(visited_nodes) = player.get_visited_nodes() {
+        println!("player {} visited nodes: {}", index + 1, visited_nodes);
+      }
+    }
+
+    for (index, player) in players.iter().enumerate() {
+      if let Some(playouts) = player.get_playouts() {
+        println!("player {} playouts: {}", index + 1, playouts);
+      }
+    }
+
+    Ok(())
+  }
+}
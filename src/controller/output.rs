@@ -0,0 +1,350 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{ControllerError, Ending, GameCount, Listener, Results};
+use crate::{game::Game, player::Player};
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How a match is printed.
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+  /// The boards after every move and the results at the end
+  Text,
+  /// One JSON object per line for every game and one for the results at the end
+  Json,
+  /// One row for every game and one for every player at the end, under a common header
+  Csv,
+}
+
+const CSV_HEADER: &str = "record,game,seed,x,o,moves,move_milliseconds,outcome,forfeited_by,\
+                          forfeit,player,name,wins_as_x,wins_as_o,draws,losses_on_time,visited_nodes,\
+                          playouts";
+
+#[derive(Serialize)]
+struct PlayerName {
+  player: usize,
+  name: String,
+}
+
+#[derive(Serialize)]
+struct Move {
+  square: String,
+  milliseconds: f64,
+}
+
+#[derive(Serialize)]
+struct PlayerSummary {
+  player: usize,
+  name: String,
+  wins_as_x: GameCount,
+  wins_as_o: GameCount,
+  draws: GameCount,
+  losses_on_time: GameCount,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  visited_nodes: Option<u64>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  playouts: Option<u64>,
+}
+
+/// A line of machine-readable output. Players are numbered from 1 in the order they were given.
+#[derive(Serialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum Record {
+  Game {
+    game: GameCount,
+    seed: u64,
+    x: PlayerName,
+    o: PlayerName,
+    moves: Vec<Move>,
+    outcome: String,
+    /// The side that forfeited the game, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forfeited_by: Option<String>,
+    /// Why the game was forfeited, if it was.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forfeit: Option<String>,
+  },
+  Summary {
+    games: GameCount,
+    players: Vec<PlayerSummary>,
+  },
+}
+
+impl Record {
+  fn print(&self, format: OutputFormat) -> Result<(), serde_json::Error> {
+    match format {
+      OutputFormat::Text => (),
+      OutputFormat::Json => println!("{}", serde_json::to_string(self)?),
+      OutputFormat::Csv => {
+        for row in self.get_csv_rows() {
+          println!("{}", row);
+        }
+      }
+    }
+
+    Ok(())
+  }
+
+  fn get_csv_rows(&self) -> Vec<String> {
+    match self {
+      Self::Game {
+        game,
+        seed,
+        x,
+        o,
+        moves,
+        outcome,
+        forfeited_by,
+        forfeit,
+      } => {
+        let squares: Vec<&str> = moves.iter().map(|step| step.square.as_str()).collect();
+        let milliseconds: Vec<String> = moves
+          .iter()
+          .map(|step| format!("{:.3}", step.milliseconds))
+          .collect();
+        vec![format!(
+          "game,{},{},{},{},{},{},{},{},{},,,,,,,,",
+          game,
+          seed,
+          x.player,
+          o.player,
+          squares.join(" "),
+          milliseconds.join(" "),
+          quote(outcome),
+          quote(forfeited_by.as_deref().unwrap_or_default()),
+          quote(forfeit.as_deref().unwrap_or_default())
+        )]
+      }
+      Self::Summary { players, .. } => {
+        let optional =
+          |value: Option<u64>| value.map(|value| value.to_string()).unwrap_or_default();
+
+        players
+          .iter()
+          .map(|player| {
+            format!(
+              "summary,,,,,,,,,,{},{},{},{},{},{},{},{}",
+              player.player,
+              quote(&player.name),
+              player.wins_as_x,
+              player.wins_as_o,
+              player.draws,
+              player.losses_on_time,
+              optional(player.visited_nodes),
+              optional(player.playouts)
+            )
+          })
+          .collect()
+      }
+    }
+  }
+}
+
+/// Quotes a CSV field as RFC 4180 requires when it holds a comma, a quote or a line break,
+/// doubling its quotes.
+fn quote(field: &str) -> String {
+  if field.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
+  }
+}
+
+/// Prints the games and the results of a match in a machine-readable format.
+pub struct Logger {
+  format: OutputFormat,
+}
+
+impl Logger {
+  /// Prints the match in `format`.
+  pub fn new(format: OutputFormat) -> Self {
+    Self { format }
+  }
+}
+
+impl Listener for Logger {
+  fn notify_match_start(
+    &mut self,
+    _players: &[Box<dyn Player>; 2],
+    _position: &Game,
+    _alternate: bool,
+  ) -> Result<(), ControllerError> {
+    if self.format == OutputFormat::Csv {
+      println!("{}", CSV_HEADER);
+    }
+
+    Ok(())
+  }
+
+  /// Prints the record of the finished game, with the time every move took.
+  fn notify_game_end(
+    &mut self,
+    players: &[Box<dyn Player>; 2],
+    _position: &Game,
+    _game: &Game,
+    ending: &Ending,
+  ) -> Result<(), ControllerError> {
+    let x = ending.x;
+    let name = |index: usize| PlayerName {
+      player: index + 1,
+      name: players[index].to_string(),
+    };
+
+    Record::Game {
+      game: ending.number,
+      seed: ending.seed,
+      x: name(x),
+      o: name(1 - x),
+      moves: ending
+        .moves
+        .iter()
+        .map(|(square, duration)| Move {
+          square: square.to_string(),
+          milliseconds: duration.as_micros() as f64 / 1000.0,
+        })
+        .collect(),
+      outcome: ending.outcome.to_string(),
+      forfeited_by: ending.forfeit.as_ref().map(|(side, _)| side.to_string()),
+      forfeit: ending
+        .forfeit
+        .as_ref()
+        .map(|(_, forfeit)| forfeit.to_string()),
+    }
+    .print(self.format)?;
+    Ok(())
+  }
+
+  fn notify_match_end(
+    &mut self,
+    players: &[Box<dyn Player>; 2],
+    results: &[Results; 2],
+    games: GameCount,
+  ) -> Result<(), ControllerError> {
+    Record::Summary {
+      games,
+      players: players
+        .iter()
+        .zip(results)
+        .enumerate()
+        .map(|(index, (player, results))| PlayerSummary {
+          player: index + 1,
+          name: player.to_string(),
+          wins_as_x: results.wins_as_x,
+          wins_as_o: results.wins_as_o,
+          draws: results.draws,
+          losses_on_time: results.losses_on_time,
+          visited_nodes: player.get_visited_nodes(),
+          playouts: player.get_playouts(),
+        })
+        .collect(),
+    }
+    .print(self.format)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{quote, Move, PlayerName, PlayerSummary, Record};
+
+  fn get_game() -> Record {
+    Record::Game {
+      game: 1,
+      seed: 7,
+      x: PlayerName {
+        player: 2,
+        name: "random".to_string(),
+      },
+      o: PlayerName {
+        player: 1,
+        name: "minimax".to_string(),
+      },
+      moves: vec![
+        Move {
+          square: "b2".to_string(),
+          milliseconds: 0.5,
+        },
+        Move {
+          square: "a1".to_string(),
+          milliseconds: 1.25,
+        },
+      ],
+      outcome: "draw".to_string(),
+      forfeited_by: None,
+      forfeit: None,
+    }
+  }
+
+  fn get_summary(name: &str) -> Record {
+    Record::Summary {
+      games: 1,
+      players: vec![PlayerSummary {
+        player: 1,
+        name: name.to_string(),
+        wins_as_x: 0,
+        wins_as_o: 0,
+        draws: 1,
+        losses_on_time: 0,
+        visited_nodes: Some(42),
+        playouts: None,
+      }],
+    }
+  }
+
+  #[test]
+  fn csv_fields_are_quoted_only_when_needed() {
+    assert_eq!(quote("mcts"), "mcts");
+    assert_eq!(quote("a, b"), "\"a, b\"");
+    assert_eq!(quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+    assert_eq!(quote("two\nlines"), "\"two\nlines\"");
+  }
+
+  #[test]
+  fn csv_rows_have_a_field_for_every_column() {
+    let columns = super::CSV_HEADER.split(',').count();
+    let rows = [
+      get_game().get_csv_rows(),
+      get_summary("engine, version 2").get_csv_rows(),
+    ]
+    .concat();
+
+    assert_eq!(
+      rows,
+      [
+        "game,1,7,2,1,b2 a1,0.500 1.250,draw,,,,,,,,,,",
+        "summary,,,,,,,,,,1,\"engine, version 2\",0,0,1,0,42,",
+      ]
+    );
+
+    for row in &rows {
+      assert_eq!(
+        row.replace("\"engine, version 2\"", "").split(',').count(),
+        columns
+      );
+    }
+  }
+
+  #[test]
+  fn json_records_are_tagged_and_skip_missing_values() {
+    assert_eq!(
+      serde_json::to_string(&get_game()).unwrap(),
+      r#"{"record":"game","game":1,"seed":7,"x":{"player":2,"name":"random"},"o":{"player":1,"name":"minimax"},"moves":[{"square":"b2","milliseconds":0.5},{"square":"a1","milliseconds":1.25}],"outcome":"draw"}"#
+    );
+    assert_eq!(
+      serde_json::to_string(&get_summary("minimax")).unwrap(),
+      r#"{"record":"summary","games":1,"players":[{"player":1,"name":"minimax","wins_as_x":0,"wins_as_o":0,"draws":1,"losses_on_time":0,"visited_nodes":42}]}"#
+    );
+  }
+}
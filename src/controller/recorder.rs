@@ -0,0 +1,51 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{ControllerError, Ending, Listener};
+use crate::{game::Game, player::Player, record::GameRecord};
+use std::path::PathBuf;
+
+/// Appends every finished game to a record file, which the replay command steps through.
+pub struct Recorder {
+  path: PathBuf,
+}
+
+impl Recorder {
+  /// Appends the games to the record file at `path`.
+  pub fn new(path: PathBuf) -> Self {
+    Self { path }
+  }
+}
+
+impl Listener for Recorder {
+  fn notify_game_end(
+    &mut self,
+    players: &[Box<dyn Player>; 2],
+    position: &Game,
+    game: &Game,
+    ending: &Ending,
+  ) -> Result<(), ControllerError> {
+    let x = ending.x;
+    let players = [players[x].to_string(), players[1 - x].to_string()];
+    GameRecord::new(
+      players,
+      ending.seed,
+      position,
+      game,
+      ending.forfeit.as_ref(),
+    )
+    .append(&self.path)?;
+    Ok(())
+  }
+}
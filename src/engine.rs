@@ -0,0 +1,310 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A line-based text protocol for serving players to other programs, in the spirit of UCI and
+//! GTP, and the server of the `engine` command and of `--join`, which speaks it over TCP.
+//!
+//! The program using the engine writes commands on its input, one per line:
+//!
+//! - `tictactoe` starts the session, the engine replies `id name <name>` and then `ok`.
+//! - `newgame [seed]` starts a new game, seeding the engine when a seed is given.
+//! - `position <position> [moves <square>...]` sets the position to search, written like the
+//!   positions of the command line, followed by the moves made from it, such as
+//!   `position ___/___/___ x moves b2 a1`.
+//! - `go [time <ms>] [increment <ms>] [movetime <ms>]` searches the position, with the time left
+//!   on the clock, the time added after every move and the most time the move may take. The
+//!   engine replies `bestmove <square>`, `resign` when it gives the game up, or `error <message>`
+//!   when it cannot move.
+//! - `stop` makes the engine move at once.
+//! - `quit` ends the session, as does closing the input, which also stops a search.
+//!
+//! Every `go` gets exactly one reply. Errors of the commands before it, such as a position that
+//! cannot be read, are reported in that reply. Other lines of the engine start with `info` and
+//! can be ignored. A host sends the position once more when a game is over, so that a person
+//! who joined sees how it ended.
+
+use super::{
+  game::{Game, GameError, Square},
+  player::{Action, Clock, Player, PlayerError, UserError},
+};
+use std::{
+  io::{self, BufRead, BufReader, ErrorKind, Write},
+  net::{TcpStream, ToSocketAddrs},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, Arc,
+  },
+  thread,
+  time::Duration,
+};
+use thiserror::Error;
+
+/// Errors of serving a player.
+#[derive(Error, Debug)]
+pub enum EngineError {
+  /// Reading a command or writing a reply failed.
+  #[error(transparent)]
+  Io(#[from] io::Error),
+  /// The player, of the given type, waits for a person.
+  #[error("{0} players wait for a person and cannot be served as engines")]
+  InteractivePlayer(String),
+}
+
+/// Errors of the commands of a session, which the engine replies with.
+#[derive(Error, Debug)]
+enum CommandError {
+  #[error(transparent)]
+  Game(#[from] GameError),
+  #[error(transparent)]
+  Player(#[from] PlayerError),
+  #[error("'{0}' is not a seed")]
+  InvalidSeed(String),
+  #[error("'{0}' is not an argument of go")]
+  InvalidArgument(String),
+  #[error("no position has been set")]
+  NoPosition,
+}
+
+/// The position a program has set: the position it starts from and the moves made since.
+struct Position {
+  start: Game,
+  moves: Vec<Square>,
+}
+
+impl Position {
+  fn get_game(&self) -> Result<Game, GameError> {
+    let mut game = self.start.clone();
+    for square in &self.moves {
+      game.place_mark(square)?;
+    }
+    Ok(game)
+  }
+}
+
+/// Serves `player` over the protocol, reading commands from `input` and writing replies to
+/// `output` until the session ends.
+pub fn serve(
+  player: Box<dyn Player>,
+  input: impl BufRead + Send + 'static,
+  output: impl Write,
+) -> Result<(), EngineError> {
+  run(player, input, output, false)
+}
+
+/// Joins the game hosted at `address` and serves `player` to the host until it leaves. Users
+/// can play this way, since the protocol is not spoken on the standard input, and they are
+/// shown the board before every move and at the end of every game.
+pub fn join(player: Box<dyn Player>, address: impl ToSocketAddrs) -> Result<(), EngineError> {
+  let stream = TcpStream::connect(address)?;
+  let input = BufReader::new(stream.try_clone()?);
+  run(player, input, stream, true)
+}
+
+/// Serves `player`, printing the positions set when `display` is set.
+fn run(
+  mut player: Box<dyn Player>,
+  input: impl BufRead + Send + 'static,
+  mut output: impl Write,
+  display: bool,
+) -> Result<(), EngineError> {
+  let stop = Arc::new(AtomicBool::new(false));
+  let commands = read_commands(input, stop.clone());
+  let mut position = None;
+  let mut failure = None;
+
+  for line in commands {
+    let mut words = line.split_whitespace();
+
+    let reply = match words.next() {
+      None => continue,
+      Some("tictactoe") => Some(format!("id name {}\nok", player.get_name())),
+      Some("newgame") => {
+        match words.next() {
+          Some(seed) => match seed.parse() {
+            Ok(seed) => player.new_game(seed),
+            Err(_) => failure = Some(CommandError::InvalidSeed(seed.to_string())),
+          },
+          None => player.new_game(rand::random()),
+        }
+        position = None;
+        None
+      }
+      Some("position") => {
+        match set_position(player.as_mut(), position.as_ref(), &line) {
+          Ok(next) => {
+            if let (true, Ok(game)) = (display, next.get_game()) {
+              show(&game);
+            }
+            position = Some(next);
+          }
+          Err(error) => failure = Some(error),
+        }
+        None
+      }
+      Some("go") => {
+        let result = match failure.take() {
+          Some(error) => Err(error),
+          None => search(player.as_mut(), position.as_ref(), words, &stop),
+        };
+        Some(match result {
+          Ok(Action::Resign) => String::from("resign"),
+          Ok(Action::Mark(square)) => format!("bestmove {}", square),
+          Ok(_) => unreachable!("moves cannot be taken back over the protocol"),
+          Err(error) => format!("error {}", error),
+        })
+      }
+      Some("stop") => None,
+      Some("quit") => break,
+      Some(command) => Some(format!("info '{}' is not a command", command)),
+    };
+
+    if let Some(reply) = reply {
+      // The other side may have left while the player was thinking.
+      match writeln!(output, "{}", reply).and_then(|()| output.flush()) {
+        Err(error)
+          if matches!(
+            error.kind(),
+            ErrorKind::BrokenPipe | ErrorKind::ConnectionReset
+          ) =>
+        {
+          break
+        }
+        result => result?,
+      }
+    }
+  }
+
+  Ok(())
+}
+
+/// Reads the lines of `input` on a thread of its own, so that `stop` can be set while the
+/// player searches. `go` clears it again, so that a `stop` only ends the search before it.
+fn read_commands(
+  input: impl BufRead + Send + 'static,
+  stop: Arc<AtomicBool>,
+) -> mpsc::Receiver<String> {
+  let (sender, receiver) = mpsc::channel();
+
+  thread::spawn(move || {
+    for line in input.lines() {
+      let Ok(line) = line else { break };
+
+      match line.split_whitespace().next() {
+        Some("go") => stop.store(false, Ordering::Relaxed),
+        Some("stop" | "quit") => stop.store(true, Ordering::Relaxed),
+        _ => (),
+      }
+
+      if sender.send(line).is_err() {
+        break;
+      }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+  });
+
+  receiver
+}
+
+/// Reads a `position` command and tells `player` about the moves that changed since `previous`,
+/// taking back those that are no longer made, so that it can keep what it learned about them.
+fn set_position(
+  player: &mut dyn Player,
+  previous: Option<&Position>,
+  line: &str,
+) -> Result<Position, CommandError> {
+  let text = line.trim_start().trim_start_matches("position");
+  let (start, moves) = match text.split_once(" moves") {
+    Some((start, moves)) => (start, moves),
+    None => (text, ""),
+  };
+  let position = Position {
+    start: start.trim().parse()?,
+    moves: moves
+      .split_whitespace()
+      .map(str::parse)
+      .collect::<Result<_, _>>()?,
+  };
+
+  // The moves are checked before the player hears about any of them.
+  position.get_game()?;
+
+  let common = match previous {
+    Some(previous) if previous.start.to_string() == position.start.to_string() => previous
+      .moves
+      .iter()
+      .zip(&position.moves)
+      .take_while(|(previous, next)| previous == next)
+      .count(),
+    _ => 0,
+  };
+
+  if let Some(previous) = previous {
+    for _ in common..previous.moves.len() {
+      player.notify_undo();
+    }
+  }
+  for square in &position.moves[common..] {
+    player.notify_move(square);
+  }
+
+  Ok(position)
+}
+
+/// Prints the board of `game` with its outcome, if it is over.
+fn show(game: &Game) {
+  println!();
+  game.print_grid();
+
+  if let Some(outcome) = game.get_outcome() {
+    println!("{}", outcome);
+  }
+}
+
+/// Reads the arguments of a `go` command and asks `player` for its move in the position set.
+/// Taking back moves fails, since only the host can do that.
+fn search<'a>(
+  player: &mut dyn Player,
+  position: Option<&Position>,
+  mut arguments: impl Iterator<Item = &'a str>,
+  stop: &Arc<AtomicBool>,
+) -> Result<Action, CommandError> {
+  let position = position.ok_or(CommandError::NoPosition)?;
+  let mut remaining = Duration::MAX;
+  let mut increment = Duration::ZERO;
+  let mut move_limit = None;
+
+  while let Some(name) = arguments.next() {
+    let milliseconds = arguments
+      .next()
+      .and_then(|value| value.parse().ok())
+      .map(Duration::from_millis)
+      .ok_or_else(|| CommandError::InvalidArgument(name.to_string()))?;
+
+    match name {
+      "time" => remaining = milliseconds,
+      "increment" => increment = milliseconds,
+      "movetime" => move_limit = Some(milliseconds),
+      _ => return Err(CommandError::InvalidArgument(name.to_string())),
+    }
+  }
+
+  let game = position.get_game()?;
+  let clock = Clock::new(remaining, increment, move_limit).with_stop(stop.clone());
+
+  match player.get_action(&game, Some(&clock))? {
+    Action::Undo | Action::Redo => Err(PlayerError::from(UserError::TakeBackRefused).into()),
+    action => Ok(action),
+  }
+}
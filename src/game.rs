@@ -12,215 +12,416 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Boards, marks and the rules deciding when a game is over.
+
+mod bitboard;
+mod renju;
+use bitboard::Bitboard;
+use clap::ValueEnum;
+use std::{cmp::Reverse, str::FromStr, sync::Arc};
 use thiserror::Error;
 
+/// Errors of moves, variants and positions.
 #[derive(Error, Debug)]
-pub(super) enum GameError {
+pub enum GameError {
+  /// A mark was placed after the game ended the given way.
   #[error("the game is already over ({0})")]
   GameIsOver(Outcome),
+  /// The square already holds a mark.
   #[error("{0} square is not empty")]
   SquareIsNotEmpty(Square),
+  /// The square is beyond the edge of the board.
+  #[error("{0} square is not on the board")]
+  SquareIsOffTheBoard(Square),
+  /// The square would give x a forbidden row under the renju rule.
+  #[error("{0} square is forbidden for x by the renju rule")]
+  SquareIsForbidden(Square),
+  /// The text is not a square.
+  #[error(
+    "'{0}' is not a square, squares are written as a column letter and a row number, such as b2"
+  )]
+  InvalidSquare(String),
+  /// The board, of the given width and height, is too small or too large.
+  #[error("a {0}x{1} board is not supported")]
+  UnsupportedBoard(u8, u8),
+  /// The row of the given length does not fit on a board of the given width and height.
+  #[error("{0} in a row does not fit on a {1}x{2} board")]
+  UnreachableRow(u8, u8, u8),
+  /// The renju rule was chosen for rows too short for it.
+  #[error("the renju rule needs at least {} in a row", Variant::RENJU_MINIMUM_K)]
+  RenjuRowTooShort,
+  /// No mark has been placed that could be taken back.
+  #[error("there is no mark to take back")]
+  NothingToUndo,
+  /// No mark has been taken back that could be placed again.
+  #[error("there is no mark to place again")]
+  NothingToRedo,
+  /// The text is not a position.
+  #[error(
+    "'{0}' is not a position, positions are written as the rows from the top separated by /, \
+     with x, o and _ for an empty square, followed by the side to move and optionally by k and \
+     the rule, such as xo_/_x_/__o x"
+  )]
+  InvalidPosition(String),
+  /// The position cannot be reached in a game.
+  #[error("the position cannot occur in a game: {0}")]
+  IllegalPosition(Illegality),
 }
 
-type Bitboard = u32;
-
-trait BitboardConstants {
-  const TOP_LEFT_X: Bitboard = 0b1;
-  const TOP_MIDDLE_X: Bitboard = Self::TOP_LEFT_X << 1;
-  const TOP_RIGHT_X: Bitboard = Self::TOP_MIDDLE_X << 1;
-  const MIDDLE_LEFT_X: Bitboard = Self::TOP_RIGHT_X << 1;
-  const MIDDLE_MIDDLE_X: Bitboard = Self::MIDDLE_LEFT_X << 1;
-  const MIDDLE_RIGHT_X: Bitboard = Self::MIDDLE_MIDDLE_X << 1;
-  const BOTTOM_LEFT_X: Bitboard = Self::MIDDLE_RIGHT_X << 1;
-  const BOTTOM_MIDDLE_X: Bitboard = Self::BOTTOM_LEFT_X << 1;
-  const BOTTOM_RIGHT_X: Bitboard = Self::BOTTOM_MIDDLE_X << 1;
-  const TOP_LEFT_O: Bitboard = Self::BOTTOM_RIGHT_X << 1;
-  const TOP_MIDDLE_O: Bitboard = Self::TOP_LEFT_O << 1;
-  const TOP_RIGHT_O: Bitboard = Self::TOP_MIDDLE_O << 1;
-  const MIDDLE_LEFT_O: Bitboard = Self::TOP_RIGHT_O << 1;
-  const MIDDLE_MIDDLE_O: Bitboard = Self::MIDDLE_LEFT_O << 1;
-  const MIDDLE_RIGHT_O: Bitboard = Self::MIDDLE_MIDDLE_O << 1;
-  const BOTTOM_LEFT_O: Bitboard = Self::MIDDLE_RIGHT_O << 1;
-  const BOTTOM_MIDDLE_O: Bitboard = Self::BOTTOM_LEFT_O << 1;
-  const BOTTOM_RIGHT_O: Bitboard = Self::BOTTOM_MIDDLE_O << 1;
-  const EMPTY_SQUARE: Bitboard = !Bitboard::TOP_LEFT_X & Bitboard::TOP_LEFT_X;
-  const X_MASK: Bitboard = Self::TOP_LEFT_X
-    | Self::TOP_MIDDLE_X
-    | Self::TOP_RIGHT_X
-    | Self::MIDDLE_LEFT_X
-    | Self::MIDDLE_MIDDLE_X
-    | Self::MIDDLE_RIGHT_X
-    | Self::BOTTOM_LEFT_X
-    | Self::BOTTOM_MIDDLE_X
-    | Self::BOTTOM_RIGHT_X;
-  const TOP_LEFT_TO_BOTTOM_RIGHT_X: Bitboard =
-    Self::TOP_LEFT_X | Self::MIDDLE_MIDDLE_X | Self::BOTTOM_RIGHT_X;
-  const TOP_RIGHT_TO_BOTTOM_LEFT_X: Bitboard =
-    Self::TOP_RIGHT_X | Self::MIDDLE_MIDDLE_X | Self::BOTTOM_LEFT_X;
-  const ROW_TOP_X: Bitboard = Self::TOP_LEFT_X | Self::TOP_MIDDLE_X | Self::TOP_RIGHT_X;
-  const ROW_MIDDLE_X: Bitboard = Self::MIDDLE_LEFT_X | Self::MIDDLE_MIDDLE_X | Self::MIDDLE_RIGHT_X;
-  const ROW_BOTTOM_X: Bitboard = Self::BOTTOM_LEFT_X | Self::BOTTOM_MIDDLE_X | Self::BOTTOM_RIGHT_X;
-  const COLUMN_LEFT_X: Bitboard = Self::TOP_LEFT_X | Self::MIDDLE_LEFT_X | Self::BOTTOM_LEFT_X;
-  const COLUMN_MIDDLE_X: Bitboard =
-    Self::TOP_MIDDLE_X | Self::MIDDLE_MIDDLE_X | Self::BOTTOM_MIDDLE_X;
-  const COLUMN_RIGHT_X: Bitboard = Self::TOP_RIGHT_X | Self::MIDDLE_RIGHT_X | Self::BOTTOM_RIGHT_X;
-  const TOP_LEFT_TO_BOTTOM_RIGHT_O: Bitboard =
-    Self::TOP_LEFT_O | Self::MIDDLE_MIDDLE_O | Self::BOTTOM_RIGHT_O;
-  const TOP_RIGHT_TO_BOTTOM_LEFT_O: Bitboard =
-    Self::TOP_RIGHT_O | Self::MIDDLE_MIDDLE_O | Self::BOTTOM_LEFT_O;
-  const ROW_TOP_O: Bitboard = Self::TOP_LEFT_O | Self::TOP_MIDDLE_O | Self::TOP_RIGHT_O;
-  const ROW_MIDDLE_O: Bitboard = Self::MIDDLE_LEFT_O | Self::MIDDLE_MIDDLE_O | Self::MIDDLE_RIGHT_O;
-  const ROW_BOTTOM_O: Bitboard = Self::BOTTOM_LEFT_O | Self::BOTTOM_MIDDLE_O | Self::BOTTOM_RIGHT_O;
-  const COLUMN_LEFT_O: Bitboard = Self::TOP_LEFT_O | Self::MIDDLE_LEFT_O | Self::BOTTOM_LEFT_O;
-  const COLUMN_MIDDLE_O: Bitboard =
-    Self::TOP_MIDDLE_O | Self::MIDDLE_MIDDLE_O | Self::BOTTOM_MIDDLE_O;
-  const COLUMN_RIGHT_O: Bitboard = Self::TOP_RIGHT_O | Self::MIDDLE_RIGHT_O | Self::BOTTOM_RIGHT_O;
-  const TOP_LEFT: Bitboard = Self::TOP_LEFT_X | Self::TOP_LEFT_O;
-  const TOP_MIDDLE: Bitboard = Self::TOP_MIDDLE_X | Self::TOP_MIDDLE_O;
-  const TOP_RIGHT: Bitboard = Self::TOP_RIGHT_X | Self::TOP_RIGHT_O;
-  const MIDDLE_LEFT: Bitboard = Self::MIDDLE_LEFT_X | Self::MIDDLE_LEFT_O;
-  const MIDDLE_MIDDLE: Bitboard = Self::MIDDLE_MIDDLE_X | Self::MIDDLE_MIDDLE_O;
-  const MIDDLE_RIGHT: Bitboard = Self::MIDDLE_RIGHT_X | Self::MIDDLE_RIGHT_O;
-  const BOTTOM_LEFT: Bitboard = Self::BOTTOM_LEFT_X | Self::BOTTOM_LEFT_O;
-  const BOTTOM_MIDDLE: Bitboard = Self::BOTTOM_MIDDLE_X | Self::BOTTOM_MIDDLE_O;
-  const BOTTOM_RIGHT: Bitboard = Self::BOTTOM_RIGHT_X | Self::BOTTOM_RIGHT_O;
-  const EMPTY: Bitboard = !Self::TOP_LEFT & Self::TOP_LEFT
-    | !Self::TOP_MIDDLE & Self::TOP_MIDDLE
-    | !Self::TOP_RIGHT & Self::TOP_RIGHT
-    | !Self::MIDDLE_LEFT & Self::MIDDLE_LEFT
-    | !Self::MIDDLE_MIDDLE & Self::MIDDLE_MIDDLE
-    | !Self::MIDDLE_RIGHT & Self::MIDDLE_RIGHT
-    | !Self::BOTTOM_LEFT & Self::BOTTOM_LEFT
-    | !Self::BOTTOM_MIDDLE & Self::BOTTOM_MIDDLE
-    | !Self::BOTTOM_RIGHT & Self::BOTTOM_RIGHT;
+/// Reasons why a position cannot be reached from the empty board.
+#[derive(Error, Debug)]
+pub enum Illegality {
+  /// The marks of x and o, in that order, differ by more than a move.
+  #[error("x has {0} marks and o has {1}, but x moves first and the sides take turns")]
+  MarkCounts(u32, u32),
+  /// The side cannot be to move with the given marks of x and o.
+  #[error("{0} cannot be to move when x has {1} marks and o has {2}")]
+  SideToMove(Side, u32, u32),
+  /// Both sides have completed a row.
+  #[error("both sides have a winning row")]
+  TwoWinners,
+  /// The side has completed a row, but the other side marked after it.
+  #[error("{0} has a winning row but did not mark last")]
+  WinnerNotLast(Side),
+  /// The side has completed rows that do not share a square.
+  #[error("{0} has winning rows that no single last mark could have completed")]
+  PlayedAfterWin(Side),
 }
 
-impl BitboardConstants for Bitboard {}
-
+/// A square of the board, given by its column counted from the left and its row counted from
+/// the bottom, both starting at 0. It is written as a column letter followed by a row number,
+/// so `a1` is the bottom left square.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
-pub(super) enum Square {
-  TopLeft,
-  TopMiddle,
-  TopRight,
-  MiddleLeft,
-  MiddleMiddle,
-  MiddleRight,
-  BottomLeft,
-  BottomMiddle,
-  BottomRight,
+pub struct Square {
+  column: u8,
+  row: u8,
 }
 
 impl Square {
-  pub(super) const COUNT: u8 = 9;
-
-  fn get_bitboard_square(&self, side: &Side) -> BitboardSquare {
-    match side {
-      Side::X => match self {
-        Self::TopLeft => BitboardSquare::TopLeftX,
-        Self::TopMiddle => BitboardSquare::TopMiddleX,
-        Self::TopRight => BitboardSquare::TopRightX,
-        Self::MiddleLeft => BitboardSquare::MiddleLeftX,
-        Self::MiddleMiddle => BitboardSquare::MiddleMiddleX,
-        Self::MiddleRight => BitboardSquare::MiddleRightX,
-        Self::BottomLeft => BitboardSquare::BottomLeftX,
-        Self::BottomMiddle => BitboardSquare::BottomMiddleX,
-        Self::BottomRight => BitboardSquare::BottomRightX,
-      },
-      Side::O => match self {
-        Self::TopLeft => BitboardSquare::TopLeftO,
-        Self::TopMiddle => BitboardSquare::TopMiddleO,
-        Self::TopRight => BitboardSquare::TopRightO,
-        Self::MiddleLeft => BitboardSquare::MiddleLeftO,
-        Self::MiddleMiddle => BitboardSquare::MiddleMiddleO,
-        Self::MiddleRight => BitboardSquare::MiddleRightO,
-        Self::BottomLeft => BitboardSquare::BottomLeftO,
-        Self::BottomMiddle => BitboardSquare::BottomMiddleO,
-        Self::BottomRight => BitboardSquare::BottomRightO,
-      },
-    }
-  }
-
-  fn get_bitboard(&self) -> Bitboard {
-    match self {
-      Self::TopLeft => Bitboard::TOP_LEFT,
-      Self::TopMiddle => Bitboard::TOP_MIDDLE,
-      Self::TopRight => Bitboard::TOP_RIGHT,
-      Self::MiddleLeft => Bitboard::MIDDLE_LEFT,
-      Self::MiddleMiddle => Bitboard::MIDDLE_MIDDLE,
-      Self::MiddleRight => Bitboard::MIDDLE_RIGHT,
-      Self::BottomLeft => Bitboard::BOTTOM_LEFT,
-      Self::BottomMiddle => Bitboard::BOTTOM_MIDDLE,
-      Self::BottomRight => Bitboard::BOTTOM_RIGHT,
-    }
+  /// Column 0 is `a` and row 0 is `1`.
+  pub fn new(column: u8, row: u8) -> Self {
+    Self { column, row }
   }
 }
 
 impl std::fmt::Display for Square {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-    match self {
-      Self::TopLeft => write!(f, "top left"),
-      Self::TopMiddle => write!(f, "top middle"),
-      Self::TopRight => write!(f, "top right"),
-      Self::MiddleLeft => write!(f, "middle left"),
-      Self::MiddleMiddle => write!(f, "middle middle"),
-      Self::MiddleRight => write!(f, "middle right"),
-      Self::BottomLeft => write!(f, "bottom left"),
-      Self::BottomMiddle => write!(f, "bottom middle"),
-      Self::BottomRight => write!(f, "bottom right"),
+    write!(f, "{}{}", (b'a' + self.column) as char, self.row as u16 + 1)
+  }
+}
+
+impl FromStr for Square {
+  type Err = GameError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let invalid = || GameError::InvalidSquare(text.to_string());
+    let mut characters = text.chars();
+
+    let column = match characters.next() {
+      Some(letter @ 'a'..='z') => letter as u8 - b'a',
+      _ => return Err(invalid()),
+    };
+
+    match characters.as_str().parse::<u8>() {
+      Ok(row @ 1..) => Ok(Self::new(column, row - 1)),
+      _ => Err(invalid()),
     }
   }
 }
 
-enum BitboardSquare {
-  TopLeftX,
-  TopMiddleX,
-  TopRightX,
-  MiddleLeftX,
-  MiddleMiddleX,
-  MiddleRightX,
-  BottomLeftX,
-  BottomMiddleX,
-  BottomRightX,
-  TopLeftO,
-  TopMiddleO,
-  TopRightO,
-  MiddleLeftO,
-  MiddleMiddleO,
-  MiddleRightO,
-  BottomLeftO,
-  BottomMiddleO,
-  BottomRightO,
+/// Decides which rows of marks win.
+#[derive(ValueEnum, Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Rule {
+  /// k or more marks in a row win
+  Freestyle,
+  /// Exactly k marks in a row win, longer rows do not count
+  Exact,
+  /// x needs exactly k marks in a row and may not make longer rows, two fours or two open
+  /// threes at once, while o wins with k or more
+  Renju,
 }
 
-impl BitboardSquare {
-  fn get_bitboard(&self) -> Bitboard {
+impl std::fmt::Display for Rule {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     match self {
-      Self::TopLeftX => Bitboard::TOP_LEFT_X,
-      Self::TopMiddleX => Bitboard::TOP_MIDDLE_X,
-      Self::TopRightX => Bitboard::TOP_RIGHT_X,
-      Self::MiddleLeftX => Bitboard::MIDDLE_LEFT_X,
-      Self::MiddleMiddleX => Bitboard::MIDDLE_MIDDLE_X,
-      Self::MiddleRightX => Bitboard::MIDDLE_RIGHT_X,
-      Self::BottomLeftX => Bitboard::BOTTOM_LEFT_X,
-      Self::BottomMiddleX => Bitboard::BOTTOM_MIDDLE_X,
-      Self::BottomRightX => Bitboard::BOTTOM_RIGHT_X,
-      Self::TopLeftO => Bitboard::TOP_LEFT_O,
-      Self::TopMiddleO => Bitboard::TOP_MIDDLE_O,
-      Self::TopRightO => Bitboard::TOP_RIGHT_O,
-      Self::MiddleLeftO => Bitboard::MIDDLE_LEFT_O,
-      Self::MiddleMiddleO => Bitboard::MIDDLE_MIDDLE_O,
-      Self::MiddleRightO => Bitboard::MIDDLE_RIGHT_O,
-      Self::BottomLeftO => Bitboard::BOTTOM_LEFT_O,
-      Self::BottomMiddleO => Bitboard::BOTTOM_MIDDLE_O,
-      Self::BottomRightO => Bitboard::BOTTOM_RIGHT_O,
+      Self::Freestyle => write!(f, "freestyle"),
+      Self::Exact => write!(f, "exact"),
+      Self::Renju => write!(f, "renju"),
+    }
+  }
+}
+
+/// Dimensions of the board, the number of marks in a row needed to win and the rule deciding
+/// which rows count.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub struct Variant {
+  width: u8,
+  height: u8,
+  k: u8,
+  rule: Rule,
+}
+
+impl Variant {
+  /// The classic game, three in a row on a 3x3 board.
+  pub const TIC_TAC_TOE: Variant = Variant {
+    width: 3,
+    height: 3,
+    k: 3,
+    rule: Rule::Freestyle,
+  };
+  /// Five in a row on a 15x15 board.
+  pub const GOMOKU: Variant = Variant {
+    width: 15,
+    height: 15,
+    k: 5,
+    rule: Rule::Freestyle,
+  };
+  /// Columns are lettered from `a` to `z`.
+  pub const MAXIMUM_WIDTH: u8 = 26;
+  /// The renju rule forbids shapes that only make sense for rows of at least five marks.
+  pub const RENJU_MINIMUM_K: u8 = 5;
+
+  /// Boards are at most [`Variant::MAXIMUM_WIDTH`] squares wide and, together with one spare
+  /// column per row, must fit in a bitboard.
+  pub fn new(width: u8, height: u8, k: u8, rule: Rule) -> Result<Self, GameError> {
+    if width == 0
+      || height == 0
+      || width > Self::MAXIMUM_WIDTH
+      || (width as usize + 1) * height as usize > Bitboard::BITS
+    {
+      return Err(GameError::UnsupportedBoard(width, height));
+    }
+
+    if k == 0 || k > width.max(height) {
+      return Err(GameError::UnreachableRow(k, width, height));
+    }
+
+    if rule == Rule::Renju && k < Self::RENJU_MINIMUM_K {
+      return Err(GameError::RenjuRowTooShort);
+    }
+
+    Ok(Self {
+      width,
+      height,
+      k,
+      rule,
+    })
+  }
+
+  /// Returns the number of columns of the board.
+  pub fn get_width(&self) -> u8 {
+    self.width
+  }
+
+  /// Returns the number of rows of the board.
+  pub fn get_height(&self) -> u8 {
+    self.height
+  }
+
+  /// Returns the number of marks in a row that wins.
+  pub fn get_k(&self) -> u8 {
+    self.k
+  }
+
+  /// Returns the rule deciding which rows win.
+  pub fn get_rule(&self) -> Rule {
+    self.rule
+  }
+
+  /// Returns the number of squares of the board.
+  pub fn get_square_count(&self) -> usize {
+    self.width as usize * self.height as usize
+  }
+}
+
+impl std::fmt::Display for Variant {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}x{}, {} in a row", self.width, self.height, self.k)?;
+
+    match self.rule {
+      Rule::Freestyle => Ok(()),
+      rule => write!(f, ", {} rule", rule),
     }
   }
 }
 
+/// Everything about a variant that does not change during a game, shared by all copies of a
+/// game. Squares are stored in the bitboards row by row from the bottom, with a spare empty
+/// column after each row, so that shifting a row of marks never wraps into the next row.
+#[derive(Debug, PartialEq, Eq)]
+struct Layout {
+  variant: Variant,
+  stride: usize,
+  board: Bitboard,
+  squares: Vec<Square>,
+}
+
+impl Layout {
+  fn new(variant: Variant) -> Self {
+    let stride = variant.width as usize + 1;
+    let mut board = Bitboard::EMPTY;
+    let mut squares = Vec::with_capacity(variant.get_square_count());
+
+    for row in 0..variant.height {
+      for column in 0..variant.width {
+        let square = Square::new(column, row);
+        board.insert(row as usize * stride + column as usize);
+        squares.push(square);
+      }
+    }
+
+    squares.sort_by_key(|square| {
+      (
+        Reverse(Self::count_lines(&variant, square)),
+        Self::get_distance_to_center(&variant, square),
+        Reverse(square.row),
+        square.column,
+      )
+    });
+
+    Self {
+      variant,
+      stride,
+      board,
+      squares,
+    }
+  }
+
+  /// Returns the squared distance from `square` to the center of the board, doubled so that it
+  /// stays whole on boards with an even number of columns or rows.
+  fn get_distance_to_center(variant: &Variant, square: &Square) -> i32 {
+    let column = 2 * square.column as i32 - (variant.width as i32 - 1);
+    let row = 2 * square.row as i32 - (variant.height as i32 - 1);
+    column * column + row * row
+  }
+
+  /// Counts the rows of `k` squares, in any direction, that pass through `square`.
+  fn count_lines(variant: &Variant, square: &Square) -> usize {
+    let k = variant.k as i16;
+    let is_on_board = |column: i16, row: i16| {
+      (0..variant.width as i16).contains(&column) && (0..variant.height as i16).contains(&row)
+    };
+
+    [(1, 0), (0, 1), (1, 1), (1, -1)]
+      .iter()
+      .map(|(column_step, row_step)| {
+        (0..k)
+          .filter(|offset| {
+            let column = square.column as i16 - offset * column_step;
+            let row = square.row as i16 - offset * row_step;
+            is_on_board(column, row)
+              && is_on_board(column + (k - 1) * column_step, row + (k - 1) * row_step)
+          })
+          .count()
+      })
+      .sum()
+  }
+
+  fn get_bit(&self, square: &Square) -> usize {
+    square.row as usize * self.stride + square.column as usize
+  }
+
+  fn get_square(&self, bit: usize) -> Square {
+    Square::new((bit % self.stride) as u8, (bit / self.stride) as u8)
+  }
+
+  fn is_on_board(&self, square: &Square) -> bool {
+    square.column < self.variant.width && square.row < self.variant.height
+  }
+
+  /// Returns the steps between neighbouring bits along a row, a column and both diagonals.
+  fn get_steps(&self) -> [usize; 4] {
+    [1, self.stride, self.stride + 1, self.stride - 1]
+  }
+
+  /// Returns whether `bitboard` contains `k` marks in a row, or exactly `k` when `exact` is set.
+  /// Each shift moves every mark one step back along a direction, so after `k - 1` shifts only
+  /// the marks starting a row of `k` remain. Such a row is exact when neither the square before
+  /// it nor the one after it is marked.
+  fn has_row(&self, bitboard: &Bitboard, exact: bool) -> bool {
+    let k = self.variant.k as usize;
+
+    self.get_steps().iter().any(|step| {
+      let mut row = *bitboard;
+
+      for _ in 1..k {
+        row = row & (row >> *step);
+      }
+
+      if exact {
+        row = row & !(*bitboard << *step) & !(*bitboard >> (k * step));
+      }
+
+      !row.is_empty()
+    })
+  }
+
+  /// Returns whether `bitboard` contains a winning row for `side`.
+  fn is_winning(&self, bitboard: &Bitboard, side: &Side) -> bool {
+    let exact = match self.variant.rule {
+      Rule::Freestyle => false,
+      Rule::Exact => true,
+      Rule::Renju => *side == Side::X,
+    };
+
+    self.has_row(bitboard, exact)
+  }
+
+  /// Marks every square within `distance` steps, in any direction, of a square in `bitboard`.
+  fn dilate(&self, bitboard: &Bitboard, distance: u8) -> Bitboard {
+    let mut dilated = *bitboard;
+
+    for _ in 0..distance {
+      let horizontal = (dilated | dilated << 1 | dilated >> 1) & self.board;
+      dilated = (horizontal | horizontal << self.stride | horizontal >> self.stride) & self.board;
+    }
+
+    dilated
+  }
+
+  /// Maps `square` through one of the symmetries of the board. The first four symmetries,
+  /// reflections across the axes and the rotation by 180 degrees, apply to every board; the last
+  /// four, which swap rows and columns, only to square boards.
+  fn transform(&self, square: &Square, symmetry: usize) -> Square {
+    let (last_column, last_row) = (self.variant.width - 1, self.variant.height - 1);
+    let (column, row) = (square.column, square.row);
+
+    match symmetry {
+      0 => Square::new(column, row),
+      1 => Square::new(last_column - column, row),
+      2 => Square::new(column, last_row - row),
+      3 => Square::new(last_column - column, last_row - row),
+      4 => Square::new(row, column),
+      5 => Square::new(last_row - row, column),
+      6 => Square::new(row, last_column - column),
+      _ => Square::new(last_row - row, last_column - column),
+    }
+  }
+
+  fn get_symmetries_count(&self) -> usize {
+    if self.variant.width == self.variant.height {
+      8
+    } else {
+      4
+    }
+  }
+}
+
+/// Identifies a position regardless of its orientation on the board.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Key {
+  x: Bitboard,
+  o: Bitboard,
+}
+
+/// How a game ended.
 #[derive(Clone, Debug, PartialEq)]
-pub(super) enum Outcome {
+pub enum Outcome {
+  /// Neither side completed a row before the board filled up.
   Draw,
+  /// X completed a row.
   XWin,
+  /// O completed a row.
   OWin,
 }
 
@@ -234,14 +435,39 @@ impl std::fmt::Display for Outcome {
   }
 }
 
-#[derive(Clone, PartialEq)]
-pub(super) enum Side {
+/// One of the two sides, x moving first.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Side {
+  /// The side that moves first.
   X,
+  /// The side that moves second.
   O,
 }
 
+impl std::fmt::Display for Side {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Self::X => write!(f, "x"),
+      Self::O => write!(f, "o"),
+    }
+  }
+}
+
+impl FromStr for Side {
+  type Err = ();
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    match text {
+      "x" => Ok(Self::X),
+      "o" => Ok(Self::O),
+      _ => Err(()),
+    }
+  }
+}
+
 impl Side {
-  fn switch(&mut self) {
+  /// Changes to the other side.
+  pub fn switch(&mut self) {
     *self = match self {
       Side::X => Side::O,
       Side::O => Side::X,
@@ -249,162 +475,519 @@ impl Side {
   }
 }
 
+/// Positions are written as the rows from the top separated by `/`, with `x`, `o` and `_` for an
+/// empty square, followed by the side to move and, unless they are 3 and freestyle, by `k` and
+/// the rule, such as `xo_/_x_/__o x` or `_____/_____/__x__/_____/_____ o 4 exact`.
+impl std::fmt::Display for Game {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let variant = &self.layout.variant;
+
+    for row in (0..variant.height).rev() {
+      for column in 0..variant.width {
+        let bit = self.layout.get_bit(&Square::new(column, row));
+
+        if self.x.contains(bit) {
+          write!(f, "x")?;
+        } else if self.o.contains(bit) {
+          write!(f, "o")?;
+        } else {
+          write!(f, "_")?;
+        }
+      }
+
+      if row > 0 {
+        write!(f, "/")?;
+      }
+    }
+
+    write!(f, " {}", self.side)?;
+
+    if variant.k != Variant::TIC_TAC_TOE.k || variant.rule != Rule::Freestyle {
+      write!(f, " {}", variant.k)?;
+    }
+
+    if variant.rule != Rule::Freestyle {
+      write!(f, " {}", variant.rule)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Parses the notation written by the [`Display`](std::fmt::Display) implementation. The board
+/// size follows from the rows, and positions that cannot occur in a game are rejected.
+impl FromStr for Game {
+  type Err = GameError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let invalid = || GameError::InvalidPosition(text.to_string());
+    let mut fields = text.split_whitespace();
+    let rows: Vec<&str> = fields.next().ok_or_else(invalid)?.split('/').collect();
+    let side = fields.next().ok_or_else(invalid)?;
+    let side = side.parse::<Side>().map_err(|_| invalid())?;
+
+    let k = match fields.next() {
+      Some(k) => k.parse::<u8>().map_err(|_| invalid())?,
+      None => Variant::TIC_TAC_TOE.k,
+    };
+
+    let rule = match fields.next() {
+      Some(rule) => Rule::from_str(rule, false).map_err(|_| invalid())?,
+      None => Rule::Freestyle,
+    };
+
+    let width = rows[0].len();
+
+    if fields.next().is_some() || rows.iter().any(|row| row.len() != width) {
+      return Err(invalid());
+    }
+
+    let (width, height) = match (u8::try_from(width), u8::try_from(rows.len())) {
+      (Ok(width), Ok(height)) => (width, height),
+      _ => return Err(invalid()),
+    };
+
+    let mut game = Game::new(Variant::new(width, height, k, rule)?);
+
+    for (row, marks) in (0..height).rev().zip(rows) {
+      for (column, mark) in (0..width).zip(marks.chars()) {
+        let bit = game.layout.get_bit(&Square::new(column, row));
+
+        match mark {
+          'x' => game.x.insert(bit),
+          'o' => game.o.insert(bit),
+          '_' => (),
+          _ => return Err(invalid()),
+        }
+      }
+    }
+
+    game.set_side(side)?;
+    Ok(game)
+  }
+}
+
+impl PartialEq for Game {
+  fn eq(&self, other: &Self) -> bool {
+    self.layout.variant == other.layout.variant
+      && self.outcome == other.outcome
+      && self.side == other.side
+      && self.x == other.x
+      && self.o == other.o
+  }
+}
+
 #[derive(PartialEq)]
 enum SquareState {
   Empty,
   Occupied,
 }
 
+/// A game in progress together with its move history. Marks taken back with
+/// [`Game::undo_mark`] can be placed again with [`Game::redo_mark`] until a different mark is
+/// placed. Games are equal when their positions are, whatever moves led to them.
 #[derive(Clone)]
-pub(super) struct Game {
+pub struct Game {
+  layout: Arc<Layout>,
   outcome: Option<Outcome>,
   side: Side,
-  bitboard: Bitboard,
+  x: Bitboard,
+  o: Bitboard,
+  history: Vec<Square>,
+  undone: Vec<Square>,
 }
 
 impl Game {
-  pub(super) fn new() -> Self {
+  /// Starts a game on the empty board of `variant`, with x to move.
+  pub fn new(variant: Variant) -> Self {
     Self {
+      layout: Arc::new(Layout::new(variant)),
       outcome: None,
       side: Side::X,
-      bitboard: Bitboard::EMPTY,
+      x: Bitboard::EMPTY,
+      o: Bitboard::EMPTY,
+      history: Vec::with_capacity(variant.get_square_count()),
+      undone: Vec::new(),
     }
   }
 
-  pub(super) fn place_mark(&mut self, square: &Square) -> Result<(), GameError> {
+  /// Marks `square` for the side to move, which then passes the turn. Marks taken back can no
+  /// longer be placed again afterwards.
+  pub fn place_mark(&mut self, square: &Square) -> Result<(), GameError> {
+    self.play(square)?;
+    self.undone.clear();
+    Ok(())
+  }
+
+  /// Takes back the last mark and returns its square. A game can only continue from a position
+  /// where it was not over yet, so the outcome is cleared.
+  pub fn undo_mark(&mut self) -> Result<Square, GameError> {
+    let square = self.history.pop().ok_or(GameError::NothingToUndo)?;
+    self.side.switch();
+    self.unmark(&square);
+    self.outcome = None;
+    self.undone.push(square);
+    Ok(square)
+  }
+
+  /// Places the last mark taken back again and returns its square.
+  pub fn redo_mark(&mut self) -> Result<Square, GameError> {
+    let square = self.undone.pop().ok_or(GameError::NothingToRedo)?;
+    self.play(&square)?;
+    Ok(square)
+  }
+
+  /// Returns the marked squares in the order they were marked, x first.
+  pub fn get_history(&self) -> &[Square] {
+    &self.history
+  }
+
+  /// Returns the squares taken back that can still be marked again, the next one last.
+  pub fn get_undone(&self) -> &[Square] {
+    &self.undone
+  }
+
+  fn play(&mut self, square: &Square) -> Result<(), GameError> {
     if let Some(outcome) = &self.outcome {
       return Err(GameError::GameIsOver(outcome.clone()));
     }
 
+    if !self.layout.is_on_board(square) {
+      return Err(GameError::SquareIsOffTheBoard(*square));
+    }
+
     if self.is_square_empty(square) == SquareState::Occupied {
       return Err(GameError::SquareIsNotEmpty(*square));
     }
 
-    self.mark(&square.get_bitboard_square(&self.side));
+    if self.is_forbidden(square) {
+      return Err(GameError::SquareIsForbidden(*square));
+    }
+
+    self.mark(square);
+    self.history.push(*square);
     self.update_outcome_if_necessary();
     self.side.switch();
     Ok(())
   }
 
-  pub(super) fn get_empty_squares(&self) -> Vec<Square> {
-    let mut squares = Vec::with_capacity(Square::COUNT as usize);
-
-    for square in [
-      Square::MiddleMiddle,
-      Square::TopLeft,
-      Square::TopRight,
-      Square::BottomLeft,
-      Square::BottomRight,
-      Square::TopMiddle,
-      Square::MiddleLeft,
-      Square::MiddleRight,
-      Square::BottomMiddle,
-    ] {
-      if self.is_square_empty(&square) == SquareState::Empty {
-        squares.push(square);
+  /// Returns the empty squares ordered from the ones on the most rows of `k` squares to the ones
+  /// on the fewest, and from the center outwards among those, which puts the center first, then
+  /// the corners and then the edges on the classic board. Squares forbidden by the renju rule are
+  /// left out.
+  pub fn get_empty_squares(&self) -> Vec<Square> {
+    let mut squares = Vec::with_capacity(self.get_square_count());
+
+    for square in &self.layout.squares {
+      if self.is_square_empty(square) == SquareState::Empty && !self.is_forbidden(square) {
+        squares.push(*square);
       }
     }
 
     squares
   }
 
-  pub(super) fn get_side_to_move(&self) -> &Side {
+  /// Returns the empty squares within `distance` steps of a mark, in the same order as
+  /// [`Game::get_empty_squares`]. On large boards the squares far away from every mark are
+  /// rarely worth considering. Falls back to all empty squares when none is close enough, and
+  /// to the first of them on an empty board.
+  pub(crate) fn get_candidate_squares(&self, distance: u8) -> Vec<Square> {
+    let marks = self.x | self.o;
+
+    if marks.is_empty() {
+      return self.get_empty_squares().into_iter().take(1).collect();
+    }
+
+    let candidates = self.layout.dilate(&marks, distance) & !marks;
+    let squares: Vec<Square> = self
+      .layout
+      .squares
+      .iter()
+      .filter(|square| {
+        candidates.contains(self.layout.get_bit(square)) && !self.is_forbidden(square)
+      })
+      .copied()
+      .collect();
+
+    if squares.is_empty() {
+      self.get_empty_squares()
+    } else {
+      squares
+    }
+  }
+
+  /// Returns the side whose turn it is.
+  pub fn get_side_to_move(&self) -> &Side {
     &self.side
   }
 
-  pub(super) fn get_outcome(&self) -> &Option<Outcome> {
+  /// Returns how the game ended, or `None` while it goes on.
+  pub fn get_outcome(&self) -> &Option<Outcome> {
     &self.outcome
   }
 
-  pub(super) fn print_grid(&self) {
-    println!(
-      "|{} {} {}|\n|{} {} {}|\n|{} {} {}|\n",
-      match self.bitboard & Bitboard::TOP_LEFT {
-        Bitboard::TOP_LEFT_X => 'x',
-        Bitboard::TOP_LEFT_O => 'o',
-        _ => ' ',
-      },
-      match self.bitboard & Bitboard::TOP_MIDDLE {
-        Bitboard::TOP_MIDDLE_X => 'x',
-        Bitboard::TOP_MIDDLE_O => 'o',
-        _ => ' ',
-      },
-      match self.bitboard & Bitboard::TOP_RIGHT {
-        Bitboard::TOP_RIGHT_X => 'x',
-        Bitboard::TOP_RIGHT_O => 'o',
-        _ => ' ',
-      },
-      match self.bitboard & Bitboard::MIDDLE_LEFT {
-        Bitboard::MIDDLE_LEFT_X => 'x',
-        Bitboard::MIDDLE_LEFT_O => 'o',
-        _ => ' ',
-      },
-      match self.bitboard & Bitboard::MIDDLE_MIDDLE {
-        Bitboard::MIDDLE_MIDDLE_X => 'x',
-        Bitboard::MIDDLE_MIDDLE_O => 'o',
-        _ => ' ',
-      },
-      match self.bitboard & Bitboard::MIDDLE_RIGHT {
-        Bitboard::MIDDLE_RIGHT_X => 'x',
-        Bitboard::MIDDLE_RIGHT_O => 'o',
-        _ => ' ',
-      },
-      match self.bitboard & Bitboard::BOTTOM_LEFT {
-        Bitboard::BOTTOM_LEFT_X => 'x',
-        Bitboard::BOTTOM_LEFT_O => 'o',
-        _ => ' ',
-      },
-      match self.bitboard & Bitboard::BOTTOM_MIDDLE {
-        Bitboard::BOTTOM_MIDDLE_X => 'x',
-        Bitboard::BOTTOM_MIDDLE_O => 'o',
-        _ => ' ',
-      },
-      match self.bitboard & Bitboard::BOTTOM_RIGHT {
-        Bitboard::BOTTOM_RIGHT_X => 'x',
-        Bitboard::BOTTOM_RIGHT_O => 'o',
-        _ => ' ',
+  /// Returns the board and the rule the game is played with.
+  pub fn get_variant(&self) -> &Variant {
+    &self.layout.variant
+  }
+
+  /// Returns the number of squares of the board.
+  pub fn get_square_count(&self) -> usize {
+    self.layout.variant.get_square_count()
+  }
+
+  /// Returns the position of `square` in the board, counting row by row from the bottom left.
+  pub(crate) fn get_square_index(&self, square: &Square) -> usize {
+    square.row as usize * self.layout.variant.width as usize + square.column as usize
+  }
+
+  /// Returns whether `square` lies within the board.
+  pub fn is_on_board(&self, square: &Square) -> bool {
+    self.layout.is_on_board(square)
+  }
+
+  /// Returns the side whose mark is on `square`, if any.
+  pub fn get_mark(&self, square: &Square) -> Option<Side> {
+    let bit = self.layout.get_bit(square);
+
+    if self.x.contains(bit) {
+      Some(Side::X)
+    } else if self.o.contains(bit) {
+      Some(Side::O)
+    } else {
+      None
+    }
+  }
+
+  /// Returns the number of marks on the board.
+  pub fn get_marks_count(&self) -> u8 {
+    (self.x | self.o).count() as u8
+  }
+
+  /// Returns the smallest key among all rotations and reflections of the position, so that
+  /// symmetric positions share the same key.
+  pub(crate) fn get_canonical_key(&self) -> Key {
+    let transform = |bitboard: &Bitboard, symmetry: usize| {
+      let mut transformed = Bitboard::EMPTY;
+
+      for bit in bitboard.iter() {
+        let square = self
+          .layout
+          .transform(&self.layout.get_square(bit), symmetry);
+        transformed.insert(self.layout.get_bit(&square));
       }
-    );
+
+      transformed
+    };
+
+    (0..self.layout.get_symmetries_count())
+      .map(|symmetry| Key {
+        x: transform(&self.x, symmetry),
+        o: transform(&self.o, symmetry),
+      })
+      .min()
+      .unwrap_or(Key {
+        x: self.x,
+        o: self.o,
+      })
+  }
+
+  /// Prints the board with the top row first. Boards other than the classic 3x3 one, where
+  /// squares are picked with the numeric keypad, are labelled with column letters and row
+  /// numbers.
+  pub fn print_grid(&self) {
+    println!("{}", self.format_grid(|_| None));
   }
 
+  /// Formats the board as [`Game::print_grid`] prints it, showing the label returned for an
+  /// empty square in place of a blank. Every column is as wide as the widest label.
+  pub fn format_grid(&self, label: impl Fn(&Square) -> Option<String>) -> String {
+    let variant = &self.layout.variant;
+    let labelled = variant.width != 3 || variant.height != 3;
+    let mut cells = Vec::with_capacity(self.get_square_count());
+
+    for row in (0..variant.height).rev() {
+      for column in 0..variant.width {
+        let square = Square::new(column, row);
+        let bit = self.layout.get_bit(&square);
+
+        cells.push(if self.x.contains(bit) {
+          "x".to_string()
+        } else if self.o.contains(bit) {
+          "o".to_string()
+        } else {
+          label(&square).unwrap_or_default()
+        });
+      }
+    }
+
+    let width = cells
+      .iter()
+      .map(String::len)
+      .max()
+      .unwrap_or_default()
+      .max(1);
+    let mut grid = String::new();
+
+    if labelled {
+      grid.push_str("  ");
+
+      for column in 0..variant.width {
+        grid.push_str(&format!(" {:>width$}", (b'a' + column) as char));
+      }
+
+      grid.push('\n');
+    }
+
+    for (index, row) in cells.chunks(variant.width as usize).enumerate() {
+      if labelled {
+        grid.push_str(&format!("{:>2}", variant.height as usize - index));
+      }
+
+      let row: Vec<String> = row.iter().map(|cell| format!("{:>width$}", cell)).collect();
+      grid.push_str(&format!("|{}|\n", row.join(" ")));
+    }
+
+    grid
+  }
+
+  /// Only the side that has just placed a mark can have completed a row. Under the renju rule
+  /// the game is also drawn when every empty square is forbidden for x.
   fn update_outcome_if_necessary(&mut self) {
-    if self.bitboard & Bitboard::TOP_LEFT_TO_BOTTOM_RIGHT_X == Bitboard::TOP_LEFT_TO_BOTTOM_RIGHT_X
-      || self.bitboard & Bitboard::TOP_RIGHT_TO_BOTTOM_LEFT_X
-        == Bitboard::TOP_RIGHT_TO_BOTTOM_LEFT_X
-      || self.bitboard & Bitboard::ROW_TOP_X == Bitboard::ROW_TOP_X
-      || self.bitboard & Bitboard::ROW_MIDDLE_X == Bitboard::ROW_MIDDLE_X
-      || self.bitboard & Bitboard::ROW_BOTTOM_X == Bitboard::ROW_BOTTOM_X
-      || self.bitboard & Bitboard::COLUMN_LEFT_X == Bitboard::COLUMN_LEFT_X
-      || self.bitboard & Bitboard::COLUMN_MIDDLE_X == Bitboard::COLUMN_MIDDLE_X
-      || self.bitboard & Bitboard::COLUMN_RIGHT_X == Bitboard::COLUMN_RIGHT_X
-    {
-      self.outcome = Some(Outcome::XWin);
-    } else if self.bitboard & Bitboard::TOP_LEFT_TO_BOTTOM_RIGHT_O
-      == Bitboard::TOP_LEFT_TO_BOTTOM_RIGHT_O
-      || self.bitboard & Bitboard::TOP_RIGHT_TO_BOTTOM_LEFT_O
-        == Bitboard::TOP_RIGHT_TO_BOTTOM_LEFT_O
-      || self.bitboard & Bitboard::ROW_TOP_O == Bitboard::ROW_TOP_O
-      || self.bitboard & Bitboard::ROW_MIDDLE_O == Bitboard::ROW_MIDDLE_O
-      || self.bitboard & Bitboard::ROW_BOTTOM_O == Bitboard::ROW_BOTTOM_O
-      || self.bitboard & Bitboard::COLUMN_LEFT_O == Bitboard::COLUMN_LEFT_O
-      || self.bitboard & Bitboard::COLUMN_MIDDLE_O == Bitboard::COLUMN_MIDDLE_O
-      || self.bitboard & Bitboard::COLUMN_RIGHT_O == Bitboard::COLUMN_RIGHT_O
+    let (bitboard, outcome) = match self.side {
+      Side::X => (&self.x, Outcome::XWin),
+      Side::O => (&self.o, Outcome::OWin),
+    };
+
+    if self.layout.is_winning(bitboard, &self.side) {
+      self.outcome = Some(outcome);
+    } else if self.x | self.o == self.layout.board
+      || self.layout.variant.rule == Rule::Renju
+        && self.side == Side::O
+        && self.layout.squares.iter().all(|square| {
+          self.is_square_empty(square) == SquareState::Occupied
+            || renju::is_forbidden(&self.layout, &self.x, &self.o, square)
+        })
     {
-      self.outcome = Some(Outcome::OWin);
-    } else if (self.bitboard | self.bitboard >> 9) & Bitboard::X_MASK == Bitboard::X_MASK {
       self.outcome = Some(Outcome::Draw);
     }
   }
 
-  fn is_square_empty(&self, square: &Square) -> SquareState {
-    if self.bitboard & square.get_bitboard() == Bitboard::EMPTY {
-      SquareState::Empty
+  /// Returns whether the renju rule forbids the side to move from marking the empty `square`.
+  fn is_forbidden(&self, square: &Square) -> bool {
+    self.layout.variant.rule == Rule::Renju
+      && self.side == Side::X
+      && renju::is_forbidden(&self.layout, &self.x, &self.o, square)
+  }
+
+  /// Makes `side` the side to move in a position set up mark by mark and works out its outcome,
+  /// after checking that the position can occur in a game.
+  fn set_side(&mut self, side: Side) -> Result<(), GameError> {
+    let illegal = |illegality| Err(GameError::IllegalPosition(illegality));
+    let (x, o) = (self.x.count(), self.o.count());
+
+    let last = if x == o {
+      Side::O
+    } else if x == o + 1 {
+      Side::X
     } else {
+      return illegal(Illegality::MarkCounts(x, o));
+    };
+
+    if side == last {
+      return illegal(Illegality::SideToMove(side, x, o));
+    }
+
+    let winners = [(Side::X, self.x), (Side::O, self.o)]
+      .into_iter()
+      .filter(|(side, bitboard)| self.layout.is_winning(bitboard, side))
+      .collect::<Vec<_>>();
+
+    match winners.as_slice() {
+      [_, _] => return illegal(Illegality::TwoWinners),
+      [(winner, _)] if *winner != last => {
+        return illegal(Illegality::WinnerNotLast(winner.clone()))
+      }
+      [(winner, bitboard)] => {
+        let completed = bitboard.iter().any(|bit| {
+          let mut before = *bitboard;
+          before.remove(bit);
+          !self.layout.is_winning(&before, winner)
+        });
+
+        if !completed {
+          return illegal(Illegality::PlayedAfterWin(winner.clone()));
+        }
+      }
+      _ => (),
+    }
+
+    if x > 0 {
+      self.side = last;
+      self.update_outcome_if_necessary();
+    }
+
+    self.side = side;
+    Ok(())
+  }
+
+  fn is_square_empty(&self, square: &Square) -> SquareState {
+    let bit = self.layout.get_bit(square);
+
+    if self.x.contains(bit) || self.o.contains(bit) {
       SquareState::Occupied
+    } else {
+      SquareState::Empty
+    }
+  }
+
+  fn mark(&mut self, square: &Square) {
+    let bit = self.layout.get_bit(square);
+
+    match self.side {
+      Side::X => self.x.insert(bit),
+      Side::O => self.o.insert(bit),
+    }
+  }
+
+  fn unmark(&mut self, square: &Square) {
+    let bit = self.layout.get_bit(square);
+
+    match self.side {
+      Side::X => self.x.remove(bit),
+      Side::O => self.o.remove(bit),
     }
   }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Game;
+
+  fn get_key(position: &str) -> super::Key {
+    position.parse::<Game>().unwrap().get_canonical_key()
+  }
+
+  #[test]
+  fn rotations_and_reflections_share_a_key() {
+    let corner = get_key("x__/___/___ o");
+
+    for position in ["__x/___/___ o", "___/___/x__ o", "___/___/__x o"] {
+      assert_eq!(get_key(position), corner, "{}", position);
+    }
+
+    assert_ne!(get_key("_x_/___/___ o"), corner);
+    assert_eq!(get_key("xo_/___/___ x"), get_key("x__/o__/___ x"));
+    assert_eq!(get_key("xo_/___/___ x"), get_key("___/__o/__x x"));
+    assert_ne!(get_key("xo_/___/___ x"), get_key("ox_/___/___ x"));
+  }
 
-  fn mark(&mut self, bitboard_square: &BitboardSquare) {
-    self.bitboard |= bitboard_square.get_bitboard();
+  #[test]
+  fn boards_that_are_not_square_are_only_reflected() {
+    let corner = get_key("x___/____/____ o 3");
+    assert_eq!(get_key("___x/____/____ o 3"), corner);
+    assert_eq!(get_key("____/____/___x o 3"), corner);
+    assert_ne!(get_key("_x__/____/____ o 3"), corner);
+    assert_ne!(get_key("____/x___/____ o 3"), corner);
   }
 }
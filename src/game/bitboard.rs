@@ -0,0 +1,143 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::{BitAnd, BitOr, Not, Shl, Shr};
+
+const WORDS: usize = 4;
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Fixed-size set of bits, large enough for every supported board.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct Bitboard([u64; WORDS]);
+
+impl Bitboard {
+  pub(super) const BITS: usize = WORDS * WORD_BITS;
+  pub(super) const EMPTY: Bitboard = Bitboard([0; WORDS]);
+
+  pub(super) fn contains(&self, index: usize) -> bool {
+    self.0[index / WORD_BITS] >> (index % WORD_BITS) & 1 == 1
+  }
+
+  pub(super) fn insert(&mut self, index: usize) {
+    self.0[index / WORD_BITS] |= 1 << (index % WORD_BITS);
+  }
+
+  pub(super) fn remove(&mut self, index: usize) {
+    self.0[index / WORD_BITS] &= !(1 << (index % WORD_BITS));
+  }
+
+  pub(super) fn is_empty(&self) -> bool {
+    *self == Self::EMPTY
+  }
+
+  pub(super) fn count(&self) -> u32 {
+    self.0.iter().map(|word| word.count_ones()).sum()
+  }
+
+  /// Returns the indexes of the bits that are set, in increasing order.
+  pub(super) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+    self.0.iter().enumerate().flat_map(|(word_index, word)| {
+      let mut word = *word;
+      std::iter::from_fn(move || {
+        if word == 0 {
+          return None;
+        }
+
+        let bit = word.trailing_zeros() as usize;
+        word &= word - 1;
+        Some(word_index * WORD_BITS + bit)
+      })
+    })
+  }
+}
+
+impl BitAnd for Bitboard {
+  type Output = Self;
+
+  fn bitand(self, rhs: Self) -> Self {
+    let mut result = self;
+
+    for (word, other) in result.0.iter_mut().zip(rhs.0) {
+      *word &= other;
+    }
+
+    result
+  }
+}
+
+impl BitOr for Bitboard {
+  type Output = Self;
+
+  fn bitor(self, rhs: Self) -> Self {
+    let mut result = self;
+
+    for (word, other) in result.0.iter_mut().zip(rhs.0) {
+      *word |= other;
+    }
+
+    result
+  }
+}
+
+impl Shr<usize> for Bitboard {
+  type Output = Self;
+
+  fn shr(self, rhs: usize) -> Self {
+    let mut result = Self::EMPTY;
+    let (words, bits) = (rhs / WORD_BITS, rhs % WORD_BITS);
+
+    for index in 0..WORDS.saturating_sub(words) {
+      result.0[index] = self.0[index + words] >> bits;
+
+      if bits != 0 && index + words + 1 < WORDS {
+        result.0[index] |= self.0[index + words + 1] << (WORD_BITS - bits);
+      }
+    }
+
+    result
+  }
+}
+
+impl Shl<usize> for Bitboard {
+  type Output = Self;
+
+  fn shl(self, rhs: usize) -> Self {
+    let mut result = Self::EMPTY;
+    let (words, bits) = (rhs / WORD_BITS, rhs % WORD_BITS);
+
+    for index in words..WORDS {
+      result.0[index] = self.0[index - words] << bits;
+
+      if bits != 0 && index > words {
+        result.0[index] |= self.0[index - words - 1] >> (WORD_BITS - bits);
+      }
+    }
+
+    result
+  }
+}
+
+impl Not for Bitboard {
+  type Output = Self;
+
+  fn not(self) -> Self {
+    let mut result = self;
+
+    for word in result.0.iter_mut() {
+      *word = !*word;
+    }
+
+    result
+  }
+}
@@ -0,0 +1,193 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{Bitboard, Layout, Square};
+
+const DIRECTIONS: [(i16, i16); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Cell {
+  Own,
+  Empty,
+  Blocked,
+}
+
+/// The squares around a freshly marked square along one direction, `k + 1` on each side, seen
+/// from x. A "four" is `k - 1` marks that one more mark turns into exactly `k` in a row, and a
+/// "three" is one mark short of a four open at both ends.
+struct Line {
+  cells: Vec<(Cell, Option<Square>)>,
+  k: usize,
+}
+
+impl Line {
+  fn new(
+    layout: &Layout,
+    x: &Bitboard,
+    o: &Bitboard,
+    square: &Square,
+    direction: (i16, i16),
+  ) -> Self {
+    let radius = layout.variant.k as i16 + 1;
+    let cells = (-radius..=radius)
+      .map(|offset| {
+        let column = square.column as i16 + offset * direction.0;
+        let row = square.row as i16 + offset * direction.1;
+
+        if column < 0 || row < 0 {
+          return (Cell::Blocked, None);
+        }
+
+        let square = Square::new(column as u8, row as u8);
+
+        if !layout.is_on_board(&square) || o.contains(layout.get_bit(&square)) {
+          (Cell::Blocked, None)
+        } else if x.contains(layout.get_bit(&square)) {
+          (Cell::Own, Some(square))
+        } else {
+          (Cell::Empty, Some(square))
+        }
+      })
+      .collect();
+
+    Self {
+      cells,
+      k: layout.variant.k as usize,
+    }
+  }
+
+  fn get_center(&self) -> usize {
+    self.k + 1
+  }
+
+  fn is_own(&self, index: usize) -> bool {
+    matches!(self.cells.get(index), Some((Cell::Own, _)))
+  }
+
+  fn is_empty(&self, index: usize) -> bool {
+    matches!(self.cells.get(index), Some((Cell::Empty, _)))
+  }
+
+  /// Returns whether the marks through the center run for more than `k` squares.
+  fn is_overline(&self) -> bool {
+    let center = self.get_center();
+    let before = (0..center)
+      .rev()
+      .take_while(|index| self.is_own(*index))
+      .count();
+    let after = (center + 1..self.cells.len())
+      .take_while(|index| self.is_own(*index))
+      .count();
+
+    before + 1 + after > self.k
+  }
+
+  /// Returns the only empty square of `range` when all the others hold marks.
+  fn get_gap(&self, range: std::ops::Range<usize>) -> Option<usize> {
+    let mut gap = None;
+
+    for index in range {
+      if self.is_empty(index) && gap.is_none() {
+        gap = Some(index);
+      } else if !self.is_own(index) {
+        return None;
+      }
+    }
+
+    gap
+  }
+
+  /// Counts the fours through the center. A straight four can be completed at either end but
+  /// counts once, so fours are told apart by the marks they are made of.
+  fn count_fours(&self) -> usize {
+    let center = self.get_center();
+    let mut fours: Vec<u64> = Vec::new();
+
+    for start in center + 1 - self.k..=center {
+      let Some(gap) = self.get_gap(start..start + self.k) else {
+        continue;
+      };
+
+      if self.is_own(start - 1) || self.is_own(start + self.k) {
+        continue;
+      }
+
+      let marks = (start..start + self.k)
+        .filter(|index| *index != gap)
+        .fold(0, |marks, index| marks | 1 << index);
+
+      if !fours.contains(&marks) {
+        fours.push(marks);
+      }
+    }
+
+    fours.len()
+  }
+
+  /// Returns whether a mark in a gap that is not itself forbidden would turn the marks through
+  /// the center into a straight four.
+  fn has_three(&self, layout: &Layout, x: &Bitboard, o: &Bitboard) -> bool {
+    let center = self.get_center();
+
+    (center + 1 - self.k..center).any(|start| {
+      let end = start + self.k;
+
+      if !self.is_empty(start)
+        || !self.is_empty(end)
+        || self.is_own(start - 1)
+        || self.is_own(end + 1)
+      {
+        return false;
+      }
+
+      match self.get_gap(start + 1..end) {
+        Some(gap) => match &self.cells[gap] {
+          (_, Some(square)) => !is_forbidden(layout, x, o, square),
+          (_, None) => false,
+        },
+        None => false,
+      }
+    })
+  }
+}
+
+/// Returns whether the renju rule forbids x from marking the empty `square`: a move is forbidden
+/// when it makes more than `k` in a row, two fours or two threes, unless it also makes exactly
+/// `k` in a row.
+pub(super) fn is_forbidden(layout: &Layout, x: &Bitboard, o: &Bitboard, square: &Square) -> bool {
+  let mut x = *x;
+  x.insert(layout.get_bit(square));
+
+  if layout.has_row(&x, true) {
+    return false;
+  }
+
+  let (mut fours, mut threes) = (0, 0);
+
+  for direction in DIRECTIONS {
+    let line = Line::new(layout, &x, o, square, direction);
+
+    if line.is_overline() {
+      return true;
+    }
+
+    fours += line.count_fours();
+
+    if line.has_three(layout, &x, o) {
+      threes += 1;
+    }
+  }
+
+  fours > 1 || threes > 1
+}
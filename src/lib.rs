@@ -0,0 +1,60 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The m,n,k-game, Tic-tac-toe and its larger relatives such as Gomoku, together with players
+//! that search it and a controller that plays matches between them.
+//!
+//! A [`game::Game`] holds a position and its move history. Players implement
+//! [`player::Player`], and a [`controller::Controller`] plays a match between two of them,
+//! telling its [`controller::Listener`]s about every game:
+//!
+//! ```
+//! use tic_tac_toe::{
+//!   controller::Controller,
+//!   game::{Game, Variant},
+//!   player::{Minimax, Random},
+//! };
+//!
+//! let results = Controller::new(
+//!   Box::new(Minimax::new()),
+//!   Box::new(Random::new()),
+//!   10,
+//!   true,
+//!   Game::new(Variant::TIC_TAC_TOE),
+//!   0,
+//! )
+//! .play_match()
+//! .unwrap();
+//! assert_eq!(results[1].get_wins(), 0);
+//! ```
+
+#![warn(missing_docs)]
+
+mod analysis;
+mod application;
+pub mod controller;
+pub mod engine;
+pub mod game;
+pub mod player;
+mod record;
+pub mod solver;
+mod sprt;
+mod tournament;
+
+pub use application::ApplicationError;
+
+/// Runs the command-line application with the arguments the process was started with.
+pub fn run() -> Result<(), ApplicationError> {
+  application::main()
+}
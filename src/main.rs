@@ -12,13 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-mod application;
-mod controller;
-mod game;
-mod player;
-
-fn main() -> Result<(), application::ApplicationError> {
-  match application::main() {
+fn main() -> Result<(), tic_tac_toe::ApplicationError> {
+  match tic_tac_toe::run() {
     Ok(()) => Ok(()),
     Err(error) => {
       eprintln!("error: {}", error);
@@ -12,77 +12,249 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+//! Players choosing the moves of a side, from search engines to a person at the keyboard.
+
+mod alpha_beta;
+mod connection;
+mod external;
 mod mcts;
 mod minimax;
+mod perfect;
 mod random;
+mod registry;
+mod remote;
+mod transposition;
 mod user;
 use super::game::{Game, Square};
-use clap::ValueEnum;
-use thiserror::Error;
-use {
-  mcts::Mcts, mcts::MctsError, minimax::MinimaxError, random::Random, random::RandomError,
-  user::User, user::UserError,
+pub use alpha_beta::{AlphaBeta, AlphaBetaError};
+pub use connection::ConnectionError;
+pub use external::{External, ExternalError};
+pub use mcts::{Mcts, MctsConfig, MctsError, Parallelization};
+pub use minimax::{Evaluation, Minimax, MinimaxError};
+pub use perfect::{Perfect, PerfectError};
+pub use random::{Random, RandomError};
+use registry::Entry;
+pub use registry::{Context, PlayerSpec};
+pub use remote::{Remote, RemoteError};
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+  },
+  time::Duration,
 };
+use thiserror::Error;
+pub use user::{User, UserError};
+
+/// Every player type, in the order they are listed. A new player type only has to be added here
+/// with the entry describing it.
+const ENTRIES: [&Entry; 8] = [
+  &alpha_beta::ENTRY,
+  &external::ENTRY,
+  &mcts::ENTRY,
+  &minimax::ENTRY,
+  &perfect::ENTRY,
+  &random::ENTRY,
+  &remote::ENTRY,
+  &user::ENTRY,
+];
+
+/// Derives an independent seed for the given stream from `seed`, using the SplitMix64 mixing
+/// function.
+pub(crate) fn derive_seed(seed: u64, stream: u64) -> u64 {
+  let mut z = seed.wrapping_add(stream.wrapping_add(1).wrapping_mul(0x9e3779b97f4a7c15));
+  z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+  z ^ (z >> 31)
+}
 
+/// Errors of the players and of the player types written on the command line.
 #[derive(Error, Debug)]
-pub(super) enum PlayerError {
+pub enum PlayerError {
+  /// The alpha-beta player failed.
+  #[error(transparent)]
+  AlphaBeta(#[from] AlphaBetaError),
+  /// The program or remote player on the other side of a connection failed.
+  #[error(transparent)]
+  Connection(#[from] ConnectionError),
+  /// The external program could not be started.
+  #[error(transparent)]
+  External(#[from] ExternalError),
+  /// The mcts player failed.
   #[error(transparent)]
   Mcts(#[from] MctsError),
+  /// The minimax player failed.
   #[error(transparent)]
   Minimax(#[from] MinimaxError),
+  /// The perfect player failed.
+  #[error(transparent)]
+  Perfect(#[from] PerfectError),
+  /// The random player failed.
   #[error(transparent)]
   Random(#[from] RandomError),
+  /// The remote player could not wait for a connection.
+  #[error(transparent)]
+  Remote(#[from] RemoteError),
+  /// The user player failed.
   #[error(transparent)]
   User(#[from] UserError),
+  /// The player type is not known; the known types are listed.
+  #[error("'{0}' is not a player type, the types are {1}")]
+  UnknownPlayerType(String, String),
+  /// The option is not written as name=value.
+  #[error("'{0}' is not an option, options are written as name=value")]
+  InvalidOption(String),
+  /// The player type takes no options.
+  #[error("{0} players take no options")]
+  UnexpectedOptions(String),
+  /// The player type has no such option; its options are listed.
+  #[error("'{0}' is not an option, the options are {1}")]
+  UnknownOption(String, String),
+  /// The value is not valid for the option, whose values are described.
+  #[error("'{0}' is not a valid value for the '{1}' option, which takes {2}")]
+  InvalidOptionValue(String, String, String),
 }
 
-#[derive(ValueEnum, Clone)]
-pub(super) enum PlayerType {
-  Mcts,
-  Minimax,
-  Random,
-  User,
+impl PlayerError {
+  /// Returns whether the player ran out of time before choosing its action.
+  pub fn is_out_of_time(&self) -> bool {
+    matches!(
+      self,
+      Self::User(UserError::OutOfTime) | Self::Connection(ConnectionError::OutOfTime)
+    )
+  }
+
+  /// Returns whether the engine of the player reported an error or replied with something else
+  /// than an action.
+  pub fn is_engine_failure(&self) -> bool {
+    matches!(
+      self,
+      Self::Connection(ConnectionError::Failed(_) | ConnectionError::UnexpectedReply(_))
+    )
+  }
+
+  /// Returns whether the player has left, such as a remote player whose connection was lost or
+  /// an engine that has exited.
+  pub fn is_disconnected(&self) -> bool {
+    matches!(
+      self,
+      Self::Connection(ConnectionError::Disconnected | ConnectionError::Io(_))
+    )
+  }
+}
+
+/// Time a player has left for its move under the time control of a match. A clock can also
+/// carry a flag that asks the player to stop thinking and move at once.
+#[derive(Clone)]
+pub struct Clock {
+  remaining: Duration,
+  increment: Duration,
+  move_limit: Option<Duration>,
+  stop: Option<Arc<AtomicBool>>,
 }
 
-impl PlayerType {
-  pub(super) const PLAYER_O_DEFAULT: PlayerType = PlayerType::Mcts;
-  pub(super) const PLAYER_X_DEFAULT: PlayerType = PlayerType::User;
+impl Clock {
+  /// Share of its time a player plans to spend, leaving the rest as a safety margin.
+  const SAFETY: f64 = 0.8;
 
-  pub(super) fn get_player(self) -> Player {
-    match self {
-      Self::Mcts => Player::Mcts(Mcts::new()),
-      Self::Minimax => Player::Minimax,
-      Self::Random => Player::Random(Random::new()),
-      Self::User => Player::User(User::new()),
+  /// `remaining` is the time on the player's clock, `increment` the time added after every move
+  /// and `move_limit` the most time any single move may take.
+  pub fn new(remaining: Duration, increment: Duration, move_limit: Option<Duration>) -> Self {
+    Self {
+      remaining,
+      increment,
+      move_limit,
+      stop: None,
     }
   }
+
+  /// Makes the player move as soon as `stop` is set. Players that search for a while, such as
+  /// 'mcts', stop early; the others move when they are done anyway.
+  pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+    self.stop = Some(stop);
+    self
+  }
+
+  /// Returns whether the player has been asked to move at once.
+  pub fn is_stopped(&self) -> bool {
+    self
+      .stop
+      .as_ref()
+      .is_some_and(|stop| stop.load(Ordering::Relaxed))
+  }
+
+  /// Returns the time after which the move loses on time.
+  pub fn get_deadline(&self) -> Duration {
+    self
+      .move_limit
+      .map_or(self.remaining, |limit| limit.min(self.remaining))
+  }
+
+  /// Returns the time worth spending on a move when about `moves` more moves are to be made by
+  /// the player, spreading the remaining time evenly between them.
+  pub fn get_budget(&self, moves: usize) -> Duration {
+    let share = (self.remaining / moves.max(1) as u32).saturating_add(self.increment);
+    share.min(self.get_deadline()).mul_f64(Self::SAFETY)
+  }
 }
 
-pub(super) enum Player {
-  Mcts(Mcts),
-  Minimax,
-  Random(Random),
-  User(User),
+/// What a player does on its turn. Only users take back moves or place them again.
+pub enum Action {
+  /// Places a mark on the square.
+  Mark(Square),
+  /// Takes back the last move of the side to move together with the reply to it.
+  Undo,
+  /// Places the moves taken back last again.
+  Redo,
+  /// Gives up the game, which the side to move loses.
+  Resign,
 }
 
-impl Player {
-  pub(super) fn get_move(&mut self, game: &Game) -> Result<Square, PlayerError> {
-    Ok(match self {
-      Self::Mcts(mcts) => mcts.get_move(game)?,
-      Self::Minimax => minimax::get_move(game)?,
-      Self::Random(random) => random.get_move(game)?,
-      Self::User(user) => user.get_move(game)?,
-    })
+/// Interface of every player. Only the moves are required; players that keep state between
+/// moves, such as a search tree, also follow the game through the notifications.
+pub trait Player {
+  /// Returns the name the player is introduced with.
+  fn get_name(&self) -> &str;
+
+  /// Chooses a mark for the side to move. Players that manage their time are given their
+  /// `clock` when the match has a time control.
+  fn get_move(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Square, PlayerError>;
+
+  /// Asks the player for its action, which is a move unless the player takes moves back.
+  fn get_action(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Action, PlayerError> {
+    Ok(Action::Mark(self.get_move(game, clock)?))
+  }
+
+  /// Informs the player that a new game is about to start. Players that make random choices
+  /// reseed their generators with `seed`, which makes the game reproducible.
+  fn new_game(&mut self, _seed: u64) {}
+
+  /// Informs the player about a mark placed by either side.
+  fn notify_move(&mut self, _square: &Square) {}
+
+  /// Informs the player that the last mark placed has been taken back.
+  fn notify_undo(&mut self) {}
+
+  /// Returns whether the moves of the player come from another program, whose illegal moves
+  /// forfeit the game. Illegal moves of the built-in players are bugs, which end the match.
+  fn is_external(&self) -> bool {
+    false
+  }
+
+  /// Returns the number of playouts made so far by players that sample the game tree.
+  fn get_playouts(&self) -> Option<u64> {
+    None
+  }
+
+  /// Returns the number of nodes visited so far by players that search the game tree
+  /// exhaustively.
+  fn get_visited_nodes(&self) -> Option<u64> {
+    None
   }
 }
 
-impl std::fmt::Display for Player {
+impl std::fmt::Display for dyn Player {
   fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-    match self {
-      Self::Mcts(_) => write!(f, "monte carlo tree search"),
-      Self::Minimax => write!(f, "minimax"),
-      Self::Random(_) => write!(f, "random"),
-      Self::User(_) => write!(f, "user"),
-    }
+    write!(f, "{}", self.get_name())
   }
 }
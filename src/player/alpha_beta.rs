@@ -0,0 +1,172 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+  registry::Entry,
+  transposition::{Bound, TranspositionTable},
+  Clock, Player, PlayerError,
+};
+use crate::game::{Game, GameError, Outcome, Square};
+use std::cmp;
+use thiserror::Error;
+
+/// Errors of the [`AlphaBeta`] player.
+#[derive(Error, Debug)]
+pub enum AlphaBetaError {
+  /// A move could not be placed or taken back during the search.
+  #[error(transparent)]
+  Game(#[from] GameError),
+  /// The search reached a position without empty squares.
+  #[error("no empty squares are available on the current node")]
+  NoEmptySquares,
+}
+
+type Value = i16;
+
+const WIN: Value = 512;
+const DRAW: Value = 0;
+const LOSS: Value = -WIN;
+
+pub(super) const ENTRY: Entry = Entry {
+  name: "alpha-beta",
+  options: &[],
+  interactive: false,
+  create: |_, _| Ok(Box::new(AlphaBeta::new())),
+};
+
+/// Plays perfectly with a negamax search pruned by alpha-beta bounds, remembering the values of
+/// the positions it has searched.
+pub struct AlphaBeta {
+  transposition_table: TranspositionTable<Value>,
+  visited_nodes: u64,
+}
+
+impl AlphaBeta {
+  /// Creates a player with an empty table of searched positions.
+  pub fn new() -> Self {
+    Self {
+      transposition_table: TranspositionTable::new(),
+      visited_nodes: 0,
+    }
+  }
+
+  fn get_best_square(&mut self, game: &Game) -> Result<Square, AlphaBetaError> {
+    let mut best_square = None;
+    let mut alpha = LOSS;
+    let mut node = game.clone();
+
+    for square in game.get_empty_squares() {
+      let value = self.get_child_value(&mut node, &square, LOSS, -alpha)?;
+
+      if best_square.is_none() || value > alpha {
+        alpha = value;
+        best_square = Some(square);
+      }
+    }
+
+    match best_square {
+      Some(square) => Ok(square),
+      None => Err(AlphaBetaError::NoEmptySquares),
+    }
+  }
+
+  /// Returns the value of the position reached by placing a mark on `square`, seen from the side
+  /// that places it. Wins with fewer marks on the grid are worth more than later ones. The mark
+  /// is taken back afterwards, so the whole search works on a single game.
+  fn get_child_value(
+    &mut self,
+    node: &mut Game,
+    square: &Square,
+    alpha: Value,
+    beta: Value,
+  ) -> Result<Value, AlphaBetaError> {
+    node.place_mark(square)?;
+    self.visited_nodes += 1;
+
+    let value = match node.get_outcome() {
+      Some(Outcome::Draw) => DRAW,
+      Some(_) => WIN - node.get_marks_count() as Value,
+      None => -self.negamax(node, alpha, beta)?,
+    };
+
+    node.undo_mark()?;
+    Ok(value)
+  }
+
+  /// Negamax search with alpha-beta pruning. `alpha` and `beta` bound the value seen from the
+  /// side to move; the order of `get_empty_squares` puts the strongest candidates first, which
+  /// makes cutoffs happen early.
+  fn negamax(
+    &mut self,
+    node: &mut Game,
+    mut alpha: Value,
+    mut beta: Value,
+  ) -> Result<Value, AlphaBetaError> {
+    let original_alpha = alpha;
+
+    if let Some(entry) = self.transposition_table.get(node) {
+      match entry.get_bound() {
+        Bound::Exact => return Ok(entry.get_value()),
+        Bound::Lower => alpha = cmp::max(alpha, entry.get_value()),
+        Bound::Upper => beta = cmp::min(beta, entry.get_value()),
+      }
+
+      if alpha >= beta {
+        return Ok(entry.get_value());
+      }
+    }
+
+    let mut value = LOSS;
+
+    for square in node.get_empty_squares() {
+      value = cmp::max(self.get_child_value(node, &square, -beta, -alpha)?, value);
+      alpha = cmp::max(alpha, value);
+
+      if alpha >= beta {
+        break;
+      }
+    }
+
+    let bound = if value <= original_alpha {
+      Bound::Upper
+    } else if value >= beta {
+      Bound::Lower
+    } else {
+      Bound::Exact
+    };
+
+    self.transposition_table.insert(node, value, bound);
+    Ok(value)
+  }
+}
+
+impl Default for AlphaBeta {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Player for AlphaBeta {
+  fn get_name(&self) -> &str {
+    "alpha-beta"
+  }
+
+  fn get_move(&mut self, game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    Ok(self.get_best_square(game)?)
+  }
+
+  fn get_visited_nodes(&self) -> Option<u64> {
+    Some(self.visited_nodes)
+  }
+}
@@ -0,0 +1,210 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{Action, Clock};
+use crate::game::{Game, Square};
+use std::{
+  io::{self, BufRead, BufReader, Read, Write},
+  sync::mpsc::{self, Receiver, RecvTimeoutError},
+  thread,
+  time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Errors of talking to another program over the protocol of the [`engine`](crate::engine)
+/// module.
+#[derive(Error, Debug)]
+pub enum ConnectionError {
+  /// Reading from or writing to the other side failed.
+  #[error(transparent)]
+  Io(#[from] io::Error),
+  /// The other side closed the connection.
+  #[error("the other side has disconnected")]
+  Disconnected,
+  /// The other side sent something other than a move.
+  #[error("the other side replied '{0}', which is not a move")]
+  UnexpectedReply(String),
+  /// The other side reported an error.
+  #[error("the other side failed: {0}")]
+  Failed(String),
+  /// The other side gave up the game.
+  #[error("the other side resigned")]
+  Resigned,
+  /// The other side did not move before its deadline.
+  #[error("the other side did not reply in time")]
+  OutOfTime,
+}
+
+/// A session with a program serving a player, whose lines are read on a thread of their own so
+/// that a move can run out of time while it waits for them.
+pub(super) struct Connection {
+  name: String,
+  input: Box<dyn Write + Send>,
+  lines: Receiver<io::Result<String>>,
+  pending: usize,
+}
+
+impl Connection {
+  /// Time the other side has to introduce itself.
+  const HANDSHAKE_TIME: Duration = Duration::from_secs(10);
+
+  /// Starts a session by writing commands to `input` and reading replies from `output`, and
+  /// waits for the other side to introduce itself. Its player is called `name` until it does.
+  pub(super) fn new(
+    name: &str,
+    input: impl Write + Send + 'static,
+    output: impl Read + Send + 'static,
+  ) -> Result<Self, ConnectionError> {
+    let (sender, lines) = mpsc::channel();
+    thread::spawn(move || {
+      for line in BufReader::new(output).lines() {
+        if sender.send(line).is_err() {
+          break;
+        }
+      }
+    });
+
+    let mut connection = Self {
+      name: name.to_string(),
+      input: Box::new(input),
+      lines,
+      pending: 0,
+    };
+    connection.send("tictactoe")?;
+    let deadline = Instant::now() + Self::HANDSHAKE_TIME;
+
+    loop {
+      let line = connection.receive(Some(deadline))?;
+
+      if let Some(name) = line.strip_prefix("id name ") {
+        connection.name = name.to_string();
+      } else if let Some(message) = line.strip_prefix("error ") {
+        return Err(ConnectionError::Failed(message.to_string()));
+      } else if line == "ok" {
+        return Ok(connection);
+      }
+    }
+  }
+
+  pub(super) fn get_name(&self) -> &str {
+    &self.name
+  }
+
+  pub(super) fn send(&mut self, line: &str) -> Result<(), ConnectionError> {
+    writeln!(self.input, "{}", line)?;
+    self.input.flush()?;
+    Ok(())
+  }
+
+  /// Sends the position `game` started from with the moves made since.
+  pub(super) fn send_position(&mut self, game: &Game) -> Result<(), ConnectionError> {
+    let mut start = game.clone();
+    while start.undo_mark().is_ok() {}
+    let moves: Vec<String> = game.get_history().iter().map(Square::to_string).collect();
+    self.send(&format!("position {} moves {}", start, moves.join(" ")))
+  }
+
+  /// Sends the position and, with a `clock`, the time control of the move, and waits for the
+  /// reply. Once the time for the move has run out, asks the other side to stop and ignores
+  /// the reply it sends later.
+  pub(super) fn get_action(
+    &mut self,
+    game: &Game,
+    clock: Option<&Clock>,
+  ) -> Result<Action, ConnectionError> {
+    self.send_position(game)?;
+
+    let mut go = String::from("go");
+    if let Some(clock) = clock {
+      if clock.remaining != Duration::MAX {
+        go += &format!(" time {}", clock.remaining.as_millis());
+      }
+      if !clock.increment.is_zero() {
+        go += &format!(" increment {}", clock.increment.as_millis());
+      }
+      if let Some(move_limit) = clock.move_limit {
+        go += &format!(" movetime {}", move_limit.as_millis());
+      }
+    }
+    self.send(&go)?;
+
+    let deadline = clock.and_then(|clock| Instant::now().checked_add(clock.get_deadline()));
+    match self.receive_action(deadline) {
+      Err(ConnectionError::OutOfTime) => {
+        self.send("stop")?;
+        self.pending += 1;
+        Err(ConnectionError::OutOfTime)
+      }
+      result => result,
+    }
+  }
+
+  /// Like [`Connection::get_action`], for players that cannot resign.
+  pub(super) fn get_move(
+    &mut self,
+    game: &Game,
+    clock: Option<&Clock>,
+  ) -> Result<Square, ConnectionError> {
+    match self.get_action(game, clock)? {
+      Action::Mark(square) => Ok(square),
+      _ => Err(ConnectionError::Resigned),
+    }
+  }
+
+  /// Returns the next line of the other side, waiting at most until `deadline`.
+  fn receive(&mut self, deadline: Option<Instant>) -> Result<String, ConnectionError> {
+    let line = match deadline {
+      Some(deadline) => self
+        .lines
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .map_err(|error| match error {
+          RecvTimeoutError::Timeout => ConnectionError::OutOfTime,
+          RecvTimeoutError::Disconnected => ConnectionError::Disconnected,
+        })?,
+      None => self
+        .lines
+        .recv()
+        .map_err(|_| ConnectionError::Disconnected)?,
+    }?;
+
+    Ok(line)
+  }
+
+  /// Waits for the reply to the last `go` command, skipping the replies to the searches that ran
+  /// out of time before it.
+  fn receive_action(&mut self, deadline: Option<Instant>) -> Result<Action, ConnectionError> {
+    loop {
+      let line = self.receive(deadline)?;
+
+      let reply = if let Some(square) = line.strip_prefix("bestmove ") {
+        square
+          .trim()
+          .parse()
+          .map(Action::Mark)
+          .map_err(|_| ConnectionError::UnexpectedReply(line.clone()))
+      } else if line == "resign" {
+        Ok(Action::Resign)
+      } else if let Some(message) = line.strip_prefix("error ") {
+        Err(ConnectionError::Failed(message.to_string()))
+      } else {
+        continue;
+      };
+
+      if self.pending == 0 {
+        return reply;
+      }
+      self.pending -= 1;
+    }
+  }
+}
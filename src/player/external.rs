@@ -0,0 +1,133 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+  connection::Connection,
+  registry::{Entry, OptionSpec, OptionType},
+  Action, Clock, Player, PlayerError,
+};
+use crate::game::{Game, Square};
+use std::{
+  io,
+  process::{Child, Command, Stdio},
+  thread,
+  time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Errors of the [`External`] player.
+#[derive(Error, Debug)]
+pub enum ExternalError {
+  /// The program could not be started.
+  #[error(transparent)]
+  Io(#[from] io::Error),
+  /// No command was given for the program.
+  #[error("external players need a command, such as 'external:command=tic-tac-toe engine mcts'")]
+  MissingCommand,
+}
+
+pub(super) const ENTRY: Entry = Entry {
+  name: "external",
+  options: &[OptionSpec {
+    name: "command",
+    option_type: OptionType::Text,
+  }],
+  interactive: false,
+  create: |options, _| {
+    let command = options
+      .get_text("command")
+      .ok_or(ExternalError::MissingCommand)?;
+    Ok(Box::new(External::new(command)?))
+  },
+};
+
+/// Plays the moves of another program speaking the protocol of the [`engine`](crate::engine)
+/// module, such as this one started with its `engine` command.
+pub struct External {
+  connection: Connection,
+  child: Child,
+}
+
+impl External {
+  /// Time the engine has to quit once the player is dropped, before it is killed.
+  const QUIT_TIME: Duration = Duration::from_secs(1);
+
+  /// Starts `command`, a program followed by its arguments separated by spaces, and waits for
+  /// the engine to introduce itself.
+  pub fn new(command: &str) -> Result<Self, PlayerError> {
+    let mut words = command.split_whitespace();
+    let program = words.next().ok_or(ExternalError::MissingCommand)?;
+    let mut child = Command::new(program)
+      .args(words)
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .spawn()
+      .map_err(ExternalError::from)?;
+    let (Some(input), Some(output)) = (child.stdin.take(), child.stdout.take()) else {
+      let error = io::Error::other("the engine has no standard input or output");
+      return Err(ExternalError::Io(error).into());
+    };
+
+    match Connection::new(command, input, output) {
+      Ok(connection) => Ok(Self { connection, child }),
+      Err(error) => {
+        let _ = child.kill();
+        let _ = child.wait();
+        Err(error.into())
+      }
+    }
+  }
+}
+
+impl Player for External {
+  fn get_name(&self) -> &str {
+    self.connection.get_name()
+  }
+
+  fn get_move(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    Ok(self.connection.get_move(game, clock)?)
+  }
+
+  /// Sends the position the game started from with the moves made since and, with a `clock`,
+  /// the time control of the move. The engine may resign instead of moving.
+  fn get_action(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Action, PlayerError> {
+    Ok(self.connection.get_action(game, clock)?)
+  }
+
+  fn new_game(&mut self, seed: u64) {
+    let _ = self.connection.send(&format!("newgame {}", seed));
+  }
+
+  fn is_external(&self) -> bool {
+    true
+  }
+}
+
+/// Asks the engine to quit, and kills it if it has not after a while.
+impl Drop for External {
+  fn drop(&mut self) {
+    let _ = self.connection.send("quit");
+    let deadline = Instant::now() + Self::QUIT_TIME;
+
+    while Instant::now() < deadline {
+      match self.child.try_wait() {
+        Ok(None) => thread::sleep(Duration::from_millis(10)),
+        _ => return,
+      }
+    }
+
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
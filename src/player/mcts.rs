@@ -13,237 +13,564 @@
 // limitations under the License.
 
 mod node;
-use crate::game::{Game, GameError, Outcome, Side, Square};
-use node::Node;
-use rand::{prelude::SliceRandom, rngs::ThreadRng};
-use std::collections::HashMap;
+mod tree;
+use super::{
+  registry::{Entry, OptionSpec, OptionType, Options},
+  Clock, Player, PlayerError,
+};
+use crate::game::{Game, GameError, Square};
+use clap::ValueEnum;
+use node::Proof;
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+  sync::Mutex,
+  thread::{self, ScopedJoinHandle},
+  time::{Duration, Instant},
+};
 use thiserror::Error;
+use tree::{Statistics, Tree};
 
+/// Errors of the [`Mcts`] player.
 #[derive(Error, Debug)]
-pub(crate) enum MctsError {
+pub enum MctsError {
+  /// A move could not be placed during the search.
   #[error(transparent)]
   Game(#[from] GameError),
+  /// A node that was expected to have children has none.
   #[error("child node slice is empty")]
   ChildNodesIndexesSliceEmpty,
+  /// A node whose children cover every legal move was expanded again.
+  #[error("the node has already been expanded")]
+  NodeAlreadyExpanded,
+  /// The position has no empty squares.
   #[error("no moves can be made")]
   NoSquaresAvailable,
+  /// No move could be chosen after the search.
   #[error("unable to select a move")]
   UnableToChooseMove,
+  /// A search thread panicked.
+  #[error("a search thread panicked")]
+  ThreadPanicked,
+  /// A seed was given to a search that cannot be replayed from it.
+  #[error(
+    "tree parallelization over several threads cannot replay a search from its seed, use root \
+     parallelization with --seed"
+  )]
+  NotReproducible,
 }
 
-pub(crate) struct Mcts {
-  nodes: Vec<Node>,
-  square_map: HashMap<usize, Square>,
-  random: ThreadRng,
-  outcome: Outcome,
-  node_index: usize,
+/// Searches with the settings of the command line, which the options override. The options are
+/// named like the command-line arguments without their `mcts-` prefix.
+pub(super) const ENTRY: Entry = Entry {
+  name: "mcts",
+  options: &[
+    OptionSpec {
+      name: "rounds",
+      option_type: OptionType::Integer(1, u32::MAX as u64),
+    },
+    OptionSpec {
+      name: "c",
+      option_type: OptionType::Number(0.0, f64::MAX),
+    },
+    OptionSpec {
+      name: "time-ms",
+      option_type: OptionType::Integer(0, u64::MAX),
+    },
+    OptionSpec {
+      name: "capacity",
+      option_type: OptionType::Integer(0, MctsConfig::NODES_CAPACITY_MAXIMUM as u64),
+    },
+    OptionSpec {
+      name: "threads",
+      option_type: OptionType::Integer(1, MctsConfig::THREADS_MAXIMUM as u64),
+    },
+    OptionSpec {
+      name: "parallelization",
+      option_type: OptionType::Choice(&["root", "tree"]),
+    },
+    OptionSpec {
+      name: "rave",
+      option_type: OptionType::Number(0.0, f64::MAX),
+    },
+    OptionSpec {
+      name: "neighborhood",
+      option_type: OptionType::Integer(1, u8::MAX as u64),
+    },
+  ],
+  interactive: false,
+  create: |options, context| {
+    let config = context.get_mcts_config().clone().with_options(options);
+
+    if context.is_seeded() && !config.is_reproducible() {
+      return Err(MctsError::NotReproducible.into());
+    }
+
+    Ok(Box::new(Mcts::new(config)))
+  },
+};
+
+/// How the search is split between threads. Root parallelization gives the same result for the
+/// same random generators no matter how threads are scheduled; tree parallelization does not,
+/// because the threads see each other's updates of the shared tree.
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Parallelization {
+  /// Every thread grows its own tree and the playouts of the root children are summed up.
+  Root,
+  /// All threads grow one shared tree, using virtual losses to spread out over different paths.
+  Tree,
 }
 
-impl Mcts {
-  const ROOT_NODE: usize = 0;
+/// Search parameters of the [`Mcts`] player.
+#[derive(Clone)]
+pub struct MctsConfig {
+  rounds: u32,
+  exploration: f64,
+  time_limit: Option<Duration>,
+  nodes_capacity: usize,
+  threads: usize,
+  parallelization: Parallelization,
+  rave: Option<f64>,
+  neighborhood: Option<u8>,
+}
 
-  pub(super) fn new() -> Self {
-    const NODES_CAPACITY: usize = 262144;
+impl MctsConfig {
+  /// Rounds of every search unless given otherwise.
+  pub const ROUNDS_DEFAULT: u32 = 8190;
+  /// Exploration constant of the UCT formula unless given otherwise.
+  pub const EXPLORATION_DEFAULT: f64 = std::f64::consts::SQRT_2;
+  /// Nodes the tree has room for at the start unless given otherwise.
+  pub const NODES_CAPACITY_DEFAULT: usize = 262144;
+  /// Most nodes a tree may be given room for at the start.
+  pub const NODES_CAPACITY_MAXIMUM: usize = 1 << 24;
+  /// Threads of every search unless given otherwise.
+  pub const THREADS_DEFAULT: u16 = 1;
+  /// Most threads a search may run on.
+  pub const THREADS_MAXIMUM: u16 = 256;
+
+  /// When `time_limit` is set, every search runs until the time runs out and `rounds` is
+  /// ignored.
+  pub fn new(
+    rounds: u32,
+    exploration: f64,
+    time_limit: Option<Duration>,
+    nodes_capacity: usize,
+  ) -> Self {
     Self {
-      nodes: Vec::with_capacity(NODES_CAPACITY),
-      square_map: HashMap::with_capacity(Square::COUNT as usize),
-      random: rand::thread_rng(),
-      outcome: Outcome::Draw,
-      node_index: Self::ROOT_NODE,
+      rounds,
+      exploration,
+      time_limit,
+      nodes_capacity,
+      threads: Self::THREADS_DEFAULT.into(),
+      parallelization: Parallelization::Root,
+      rave: None,
+      neighborhood: None,
     }
   }
 
-  pub(super) fn get_move(&mut self, game: &Game) -> Result<Square, MctsError> {
-    self.mcts(game)
+  /// Spreads the search over `threads` threads. The rounds are divided between the threads, so
+  /// that a fixed number of rounds measures the speedup and a time limit measures the strength
+  /// gained.
+  pub fn with_threads(mut self, threads: usize, parallelization: Parallelization) -> Self {
+    self.threads = threads;
+    self.parallelization = parallelization;
+    self
   }
 
-  fn uct(wins: f64, playouts: f64, parent_playouts: f64) -> f64 {
-    wins / playouts + std::f64::consts::SQRT_2 * (parent_playouts.ln() / playouts).sqrt()
+  /// Returns whether searches with the same seed always give the same result, which holds
+  /// unless several threads share one tree.
+  pub fn is_reproducible(&self) -> bool {
+    self.threads == 1 || self.parallelization == Parallelization::Root
   }
 
-  fn select(&mut self) {
-    self.node_index = Self::ROOT_NODE;
-
-    loop {
-      let node = &self.nodes[self.node_index];
-      let node_childrens = node.get_childrens();
+  /// Blends all-moves-as-first statistics into the UCT score, as in rapid action value
+  /// estimation. `equivalence` is the number of playouts at which the node's own mean and its
+  /// all-moves-as-first mean weigh the same; `None` leaves plain UCT.
+  pub fn with_rave(mut self, equivalence: Option<f64>) -> Self {
+    self.rave = equivalence;
+    self
+  }
 
-      if node_childrens.is_empty() {
-        return;
-      }
+  /// Restricts the search to empty squares within `distance` steps of a mark, which keeps the
+  /// tree and the playouts manageable on large boards; `None` searches every empty square.
+  pub fn with_neighborhood(mut self, distance: Option<u8>) -> Self {
+    self.neighborhood = distance;
+    self
+  }
 
-      let mut best_score = f64::MIN;
+  /// Sets the options given to the player, which have been checked against the types of
+  /// [`ENTRY`].
+  pub(super) fn with_options(mut self, options: &Options) -> Self {
+    if let Some(rounds) = options.get_integer("rounds") {
+      self.rounds = rounds as u32;
+    }
 
-      for child in node_childrens {
-        let child_node = &self.nodes[*child];
-        let child_node_playouts = child_node.get_playouts();
+    if let Some(exploration) = options.get_number("c") {
+      self.exploration = exploration;
+    }
 
-        if child_node_playouts == 0 {
-          self.node_index = *child;
-          return;
-        }
+    if let Some(time_limit) = options.get_integer("time-ms") {
+      self.time_limit = Some(Duration::from_millis(time_limit));
+    }
 
-        let score = Self::uct(
-          child_node.get_wins(),
-          child_node_playouts as f64,
-          node.get_playouts() as f64,
-        );
+    if let Some(nodes_capacity) = options.get_integer("capacity") {
+      self.nodes_capacity = nodes_capacity as usize;
+    }
 
-        if score > best_score {
-          best_score = score;
-          self.node_index = *child;
-        }
-      }
+    if let Some(threads) = options.get_integer("threads") {
+      self.threads = threads as usize;
     }
-  }
 
-  fn expand(&mut self) -> Result<(), MctsError> {
-    if self.nodes[self.node_index]
-      .get_game()
-      .get_outcome()
-      .is_some()
-    {
-      return Ok(());
+    if let Some(parallelization) = options.get_text("parallelization") {
+      self.parallelization =
+        Parallelization::from_str(parallelization, true).unwrap_or(self.parallelization);
     }
 
-    for square in self.nodes[self.node_index].get_game().get_empty_squares() {
-      let children = self.nodes.len();
-      let mut game = self.nodes[self.node_index].get_game().clone();
-      game.place_mark(&square)?;
-      self.nodes.push(Node::new(game, self.node_index));
-      self.nodes[self.node_index].add_children(children);
+    if let Some(rave) = options.get_number("rave") {
+      self.rave = Some(rave);
     }
 
-    self.node_index = *match self.nodes[self.node_index]
-      .get_childrens()
-      .choose(&mut self.random)
-    {
-      Some(index) => index,
-      None => Err(MctsError::ChildNodesIndexesSliceEmpty)?,
-    };
+    if let Some(neighborhood) = options.get_integer("neighborhood") {
+      self.neighborhood = Some(neighborhood as u8);
+    }
 
-    Ok(())
+    self
   }
+}
 
-  fn simulate(&mut self) -> Result<(), MctsError> {
-    let mut game = self.nodes[self.node_index].get_game().clone();
-
-    loop {
-      let result = game.get_outcome();
+/// Plays by Monte Carlo tree search, with a solver proving the values of the positions it can,
+/// keeping the tree from move to move.
+pub struct Mcts {
+  config: MctsConfig,
+  trees: Vec<Tree>,
+  randoms: Vec<StdRng>,
+  playouts: u64,
+}
 
-      if let Some(result) = result {
-        self.outcome = result.clone();
-        return Ok(());
-      }
+impl Mcts {
+  /// Creates a player searching with `config`.
+  pub fn new(config: MctsConfig) -> Self {
+    let trees = match config.parallelization {
+      Parallelization::Root => config.threads,
+      Parallelization::Tree => 1,
+    };
 
-      game.place_mark(match game.get_empty_squares().choose(&mut self.random) {
-        Some(square) => square,
-        None => Err(MctsError::NoSquaresAvailable)?,
-      })?;
+    Self {
+      trees: (0..trees)
+        .map(|_| Tree::new(config.nodes_capacity))
+        .collect(),
+      randoms: (0..config.threads)
+        .map(|_| StdRng::from_entropy())
+        .collect(),
+      config,
+      playouts: 0,
     }
   }
 
-  fn backpropagate(&mut self) {
-    let mut node = &mut self.nodes[self.node_index];
+  /// Runs one round of the search from `game`, the position of the root, on a single copy of it.
+  /// Returns `false` without searching once the value of the root has been proven.
+  fn round(
+    tree: &mut Tree,
+    random: &mut StdRng,
+    config: &MctsConfig,
+    game: &Game,
+  ) -> Result<bool, MctsError> {
+    if tree.is_solved() {
+      return Ok(false);
+    }
 
-    if self.outcome == Outcome::Draw {
-      loop {
-        node.add_playout();
-        node.add_draw();
+    let mut game = game.clone();
+    let node_index = tree.select(&mut game, config.exploration, config.rave)?;
+    let node_index = tree.expand(node_index, &mut game, random, config.neighborhood)?;
+    let mut simulation = Vec::with_capacity(game.get_square_count());
+    let outcome = tree::simulate(&mut game, random, config.neighborhood, &mut simulation)?;
+    tree.backpropagate(node_index, &outcome);
 
-        if self.node_index == Self::ROOT_NODE {
-          return;
-        }
+    if config.rave.is_some() {
+      tree.update_amaf(node_index, &game, &outcome, &simulation);
+    }
+
+    Ok(true)
+  }
 
-        self.node_index = node.get_parent();
-        node = &mut self.nodes[self.node_index];
+  /// Runs one round on a tree shared between threads. The lock is released during the
+  /// simulation, which is where most of the time goes.
+  fn shared_round(
+    tree: &Mutex<&mut Tree>,
+    random: &mut StdRng,
+    config: &MctsConfig,
+    game: &Game,
+  ) -> Result<bool, MctsError> {
+    let mut game = game.clone();
+    let node_index = {
+      let mut tree = tree.lock().map_err(|_| MctsError::ThreadPanicked)?;
+
+      if tree.is_solved() {
+        return Ok(false);
       }
-    }
 
-    let side = node.get_game().get_side_to_move();
+      let node_index = tree.select(&mut game, config.exploration, config.rave)?;
+      let node_index = tree.expand(node_index, &mut game, random, config.neighborhood)?;
+      tree.add_virtual_loss(node_index);
+      node_index
+    };
+
+    let mut simulation = Vec::with_capacity(game.get_square_count());
+    let outcome = tree::simulate(&mut game, random, config.neighborhood, &mut simulation)?;
+    let mut tree = tree.lock().map_err(|_| MctsError::ThreadPanicked)?;
+    tree.remove_virtual_loss(node_index);
+    tree.backpropagate(node_index, &outcome);
 
-    let mut add_win = self.outcome == Outcome::XWin && *side == Side::O
-      || self.outcome == Outcome::OWin && *side == Side::X;
+    if config.rave.is_some() {
+      tree.update_amaf(node_index, &game, &outcome, &simulation);
+    }
 
-    loop {
-      node.add_playout();
+    Ok(true)
+  }
 
-      if add_win {
-        node.add_win();
-      }
+  /// Returns the share of the rounds made by the thread with the given index.
+  fn get_rounds(config: &MctsConfig, thread: usize) -> u32 {
+    let threads = config.threads as u32;
+    config.rounds / threads + u32::from((thread as u32) < config.rounds % threads)
+  }
 
-      if self.node_index == Self::ROOT_NODE {
-        return;
+  /// Calls `round` until the time limit of the search or the given number of rounds runs out,
+  /// until `deadline` passes or the `clock` is stopped, or until `round` asks to stop, and
+  /// returns the number of rounds made.
+  fn run(
+    config: &MctsConfig,
+    rounds: u32,
+    deadline: Option<Instant>,
+    clock: Option<&Clock>,
+    mut round: impl FnMut() -> Result<bool, MctsError>,
+  ) -> Result<u64, MctsError> {
+    let mut count = 0;
+    let (rounds, deadline) = match config.time_limit {
+      Some(time_limit) => {
+        let end = Instant::now() + time_limit;
+        (
+          u64::MAX,
+          Some(deadline.map_or(end, |deadline| deadline.min(end))),
+        )
       }
+      None => (rounds as u64, deadline),
+    };
 
-      self.node_index = node.get_parent();
-      node = &mut self.nodes[self.node_index];
-      add_win = !add_win;
+    while count < rounds
+      && deadline.is_none_or(|deadline| Instant::now() < deadline)
+      && clock.is_none_or(|clock| !clock.is_stopped())
+      && round()?
+    {
+      count += 1;
     }
+
+    Ok(count)
+  }
+
+  fn join(handles: Vec<ScopedJoinHandle<Result<u64, MctsError>>>) -> Vec<Result<u64, MctsError>> {
+    handles
+      .into_iter()
+      .map(|handle| handle.join().unwrap_or(Err(MctsError::ThreadPanicked)))
+      .collect()
   }
 
-  fn search(&mut self) -> Result<(), MctsError> {
-    const ROUNDS: u32 = 8190;
+  /// Searches from `game` within the limits of the configuration. With a `clock`, the search also
+  /// stops once the share of the remaining time worth spending on the move has passed, which is
+  /// worked out from the number of moves left to make at most, or once the clock is stopped.
+  fn search(&mut self, game: &Game, clock: Option<&Clock>) -> Result<(), MctsError> {
+    let config = &self.config;
+    let deadline = clock
+      .map(|clock| clock.get_budget(game.get_empty_squares().len().div_ceil(2)))
+      .and_then(|budget| Instant::now().checked_add(budget));
+
+    let results = if config.threads == 1 {
+      let (tree, random) = (&mut self.trees[0], &mut self.randoms[0]);
+      vec![Self::run(config, config.rounds, deadline, clock, || {
+        Self::round(tree, random, config, game)
+      })]
+    } else if config.parallelization == Parallelization::Root {
+      thread::scope(|scope| {
+        Self::join(
+          self
+            .trees
+            .iter_mut()
+            .zip(self.randoms.iter_mut())
+            .enumerate()
+            .map(|(thread, (tree, random))| {
+              scope.spawn(move || {
+                Self::run(
+                  config,
+                  Self::get_rounds(config, thread),
+                  deadline,
+                  clock,
+                  || Self::round(tree, random, config, game),
+                )
+              })
+            })
+            .collect(),
+        )
+      })
+    } else {
+      let tree = Mutex::new(&mut self.trees[0]);
+      thread::scope(|scope| {
+        Self::join(
+          self
+            .randoms
+            .iter_mut()
+            .enumerate()
+            .map(|(thread, random)| {
+              let tree = &tree;
+              scope.spawn(move || {
+                Self::run(
+                  config,
+                  Self::get_rounds(config, thread),
+                  deadline,
+                  clock,
+                  || Self::shared_round(tree, random, config, game),
+                )
+              })
+            })
+            .collect(),
+        )
+      })
+    };
 
-    for _ in 0..ROUNDS {
-      self.select();
-      self.expand()?;
-      self.simulate()?;
-      self.backpropagate();
+    for result in results {
+      self.playouts += result?;
     }
 
     Ok(())
   }
 
   fn initialize(&mut self, game: &Game) -> Result<(), MctsError> {
-    self.nodes.clear();
-    self.nodes.push(Node::new(game.clone(), usize::MAX));
-    self.square_map.clear();
-
-    for square in game.get_empty_squares() {
-      let children = self.nodes.len();
-      let mut game_clone = game.clone();
-      game_clone.place_mark(&square)?;
-      self.nodes.push(Node::new(game_clone, Self::ROOT_NODE));
-      self.nodes[Self::ROOT_NODE].add_children(children);
-      self.square_map.insert(children, square);
-    }
-
-    self.node_index = *match self.nodes[Self::ROOT_NODE]
-      .get_childrens()
-      .choose(&mut self.random)
-    {
-      Some(index) => index,
-      None => Err(MctsError::ChildNodesIndexesSliceEmpty)?,
-    };
+    for (tree, random) in self.trees.iter_mut().zip(self.randoms.iter_mut()) {
+      tree.initialize(game, random, self.config.neighborhood)?;
+    }
 
-    self.simulate()?;
-    self.backpropagate();
     Ok(())
   }
 
+  /// Sums up the statistics of the moves from the root over all trees.
+  fn merge(&self) -> Vec<Statistics> {
+    let mut squares: Vec<Statistics> = Vec::new();
+
+    for tree in &self.trees {
+      for statistics in tree.get_root_statistics() {
+        match squares
+          .iter_mut()
+          .find(|other| other.square == statistics.square)
+        {
+          Some(total) => {
+            total.playouts += statistics.playouts;
+            total.wins += statistics.wins;
+            total.proof = total.proof.or(statistics.proof);
+          }
+          None => squares.push(statistics),
+        }
+      }
+    }
+
+    squares
+  }
+
+  /// Picks a square proven to win if there is one, and otherwise the square with the most
+  /// playouts, summed over all trees, among those not proven to lose.
   fn choose(&mut self) -> Result<Square, MctsError> {
-    let mut playouts = 0;
-    let mut best_square = None;
+    let squares = self.merge();
 
-    for (index, square) in &self.square_map {
-      let node_playouts = self.nodes[*index].get_playouts();
+    if let Some(statistics) = squares
+      .iter()
+      .find(|statistics| statistics.proof == Some(Proof::Win))
+    {
+      return Ok(statistics.square);
+    }
+
+    let mut best = None;
+
+    for Statistics {
+      square,
+      playouts,
+      proof,
+      ..
+    } in squares
+    {
+      let rank = (proof != Some(Proof::Loss), playouts);
 
-      if playouts < node_playouts {
-        playouts = node_playouts;
-        best_square = Some(square);
+      if best.is_none_or(|(_, best_rank)| best_rank < rank) {
+        best = Some((square, rank));
       }
     }
 
-    match best_square {
-      Some(square) => Ok(*square),
+    match best {
+      Some((square, _)) => Ok(square),
       None => Err(MctsError::UnableToChooseMove),
     }
   }
 
-  fn mcts(&mut self, game: &Game) -> Result<Square, MctsError> {
+  /// Searches `game` as for a move and returns every square searched from it together with the
+  /// playouts made through it and its mean result for the side to move, 1 being a win. Squares
+  /// whose value has been proven get that value rather than the mean of their playouts, which
+  /// stop once it is proven.
+  pub fn analyze(&mut self, game: &Game) -> Result<Vec<(Square, u32, f64)>, MctsError> {
     self.initialize(game)?;
-    self.search()?;
-    self.choose()
+    self.search(game, None)?;
+    Ok(
+      self
+        .merge()
+        .into_iter()
+        .map(|statistics| {
+          let mean = match statistics.proof {
+            Some(Proof::Win) => 1.0,
+            Some(Proof::Draw) => 0.5,
+            Some(Proof::Loss) => 0.0,
+            None => statistics.wins / f64::from(statistics.playouts.max(1)),
+          };
+          (statistics.square, statistics.playouts, mean)
+        })
+        .collect(),
+    )
+  }
+}
+
+impl Player for Mcts {
+  fn get_name(&self) -> &str {
+    "monte carlo tree search"
+  }
+
+  fn get_move(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    self.initialize(game)?;
+    self.search(game, clock)?;
+    Ok(self.choose()?)
+  }
+
+  /// Discards the trees kept from previous moves and reseeds the generator of every thread with
+  /// a seed derived from `seed`.
+  fn new_game(&mut self, seed: u64) {
+    for tree in &mut self.trees {
+      tree.clear();
+    }
+
+    for (thread, random) in self.randoms.iter_mut().enumerate() {
+      *random = StdRng::seed_from_u64(super::derive_seed(seed, thread as u64));
+    }
+  }
+
+  /// Records a move made by either side, so that the next search can continue from the subtree
+  /// of the position it leads to.
+  fn notify_move(&mut self, square: &Square) {
+    for tree in &mut self.trees {
+      tree.add_move(square);
+    }
+  }
+
+  /// Forgets the last move recorded, after it has been taken back.
+  fn notify_undo(&mut self) {
+    for tree in &mut self.trees {
+      tree.undo_move();
+    }
+  }
+
+  /// Returns the number of playouts made in all searches so far.
+  fn get_playouts(&self) -> Option<u64> {
+    Some(self.playouts)
   }
 }
@@ -12,24 +12,47 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::game::{Game, Square};
+use crate::game::{Side, Square};
 
+/// Value of a node proven by searching all of its subtree, seen from the side that marked the
+/// square leading to it.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum Proof {
+  Win,
+  Draw,
+  Loss,
+}
+
+/// A position of the search tree. Positions are not stored; they are reached by marking the
+/// squares of the nodes on the path from the root.
 pub(super) struct Node {
   childrens: Vec<usize>,
-  game: Game,
+  side: Side,
+  square: Option<Square>,
   wins: f64,
   parent: usize,
   playouts: u32,
+  virtual_losses: u32,
+  proof: Option<Proof>,
+  amaf_wins: f64,
+  amaf_playouts: u32,
+  pruned: bool,
 }
 
 impl Node {
-  pub(super) fn new(game: Game, parent: usize) -> Self {
+  pub(super) fn new(side: Side, square: Option<Square>, parent: usize) -> Self {
     Self {
-      childrens: Vec::with_capacity(Square::COUNT as usize),
-      game,
+      childrens: Vec::new(),
+      side,
+      square,
       wins: 0.0,
       parent,
       playouts: 0,
+      virtual_losses: 0,
+      proof: None,
+      amaf_wins: 0.0,
+      amaf_playouts: 0,
+      pruned: false,
     }
   }
 
@@ -37,8 +60,14 @@ impl Node {
     &self.childrens
   }
 
-  pub(super) fn get_game(&self) -> &Game {
-    &self.game
+  /// Returns the side to move in the position of this node.
+  pub(super) fn get_side(&self) -> &Side {
+    &self.side
+  }
+
+  /// Returns the square marked to reach this node from its parent.
+  pub(super) fn get_square(&self) -> &Option<Square> {
+    &self.square
   }
 
   pub(super) fn get_wins(&self) -> f64 {
@@ -53,6 +82,44 @@ impl Node {
     self.playouts
   }
 
+  /// Returns the playouts including the ones still in progress on other threads.
+  pub(super) fn get_visits(&self) -> u32 {
+    self.playouts + self.virtual_losses
+  }
+
+  pub(super) fn get_amaf_wins(&self) -> f64 {
+    self.amaf_wins
+  }
+
+  pub(super) fn get_amaf_playouts(&self) -> u32 {
+    self.amaf_playouts
+  }
+
+  /// Counts a round in which the square of this node was marked later by the same side, with
+  /// `result` being 1 for a win, 0.5 for a draw and 0 for a loss of that side.
+  pub(super) fn add_amaf(&mut self, result: f64) {
+    self.amaf_wins += result;
+    self.amaf_playouts += 1;
+  }
+
+  pub(super) fn get_proof(&self) -> Option<Proof> {
+    self.proof
+  }
+
+  pub(super) fn set_proof(&mut self, proof: Proof) {
+    self.proof = Some(proof);
+  }
+
+  /// Returns whether some legal moves were left out when the children were added, in which case
+  /// the children alone cannot prove a draw or a win of this node.
+  pub(super) fn is_pruned(&self) -> bool {
+    self.pruned
+  }
+
+  pub(super) fn set_pruned(&mut self, pruned: bool) {
+    self.pruned = pruned;
+  }
+
   pub(super) fn add_win(&mut self) {
     self.wins += 1.0;
   }
@@ -61,6 +128,10 @@ impl Node {
     self.wins += 0.5;
   }
 
+  pub(super) fn reserve_childrens(&mut self, count: usize) {
+    self.childrens.reserve_exact(count);
+  }
+
   pub(super) fn add_children(&mut self, index: usize) {
     self.childrens.push(index);
   }
@@ -68,4 +139,22 @@ impl Node {
   pub(super) fn add_playout(&mut self) {
     self.playouts += 1;
   }
+
+  pub(super) fn add_virtual_loss(&mut self) {
+    self.virtual_losses += 1;
+  }
+
+  pub(super) fn remove_virtual_loss(&mut self) {
+    self.virtual_losses -= 1;
+  }
+
+  /// Rewrites the indexes of the parent and children after the node arena has been compacted.
+  /// Nodes that were removed from the arena are mapped to `usize::MAX`.
+  pub(super) fn remap(&mut self, indexes: &[usize]) {
+    self.parent = indexes.get(self.parent).copied().unwrap_or(usize::MAX);
+
+    for children in &mut self.childrens {
+      *children = indexes[*children];
+    }
+  }
 }
@@ -0,0 +1,681 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+  node::{Node, Proof},
+  MctsError,
+};
+use crate::game::{Game, Outcome, Side, Square};
+use rand::{prelude::SliceRandom, rngs::StdRng};
+
+/// Search results of a move from the root.
+pub(super) struct Statistics {
+  pub(super) square: Square,
+  pub(super) playouts: u32,
+  pub(super) wins: f64,
+  pub(super) proof: Option<Proof>,
+}
+
+/// Node arena of a single search tree together with the position of its root and the moves
+/// made since it was last searched.
+pub(super) struct Tree {
+  nodes: Vec<Node>,
+  game: Option<Game>,
+  moves: Vec<Square>,
+}
+
+impl Tree {
+  const ROOT_NODE: usize = 0;
+
+  /// Reserves room for `nodes_capacity` nodes when that much memory is available. The arena
+  /// grows as needed either way.
+  pub(super) fn new(nodes_capacity: usize) -> Self {
+    let mut nodes = Vec::new();
+    let _ = nodes.try_reserve_exact(nodes_capacity);
+
+    Self {
+      nodes,
+      game: None,
+      moves: Vec::new(),
+    }
+  }
+
+  pub(super) fn clear(&mut self) {
+    self.nodes.clear();
+    self.game = None;
+    self.moves.clear();
+  }
+
+  pub(super) fn add_move(&mut self, square: &Square) {
+    self.moves.push(*square);
+  }
+
+  /// Forgets the last move made. When it was made before the tree was last searched, the root
+  /// no longer precedes the game and the tree is discarded.
+  pub(super) fn undo_move(&mut self) {
+    if self.moves.pop().is_none() {
+      self.clear();
+    }
+  }
+
+  /// Returns the squares that can be marked from the root together with the number of playouts
+  /// made through each of them, the wins they scored for the side to move at the root and their
+  /// proven values.
+  pub(super) fn get_root_statistics(&self) -> impl Iterator<Item = Statistics> + '_ {
+    self.nodes[Self::ROOT_NODE]
+      .get_childrens()
+      .iter()
+      .filter_map(|index| {
+        let node = &self.nodes[*index];
+        node.get_square().map(|square| Statistics {
+          square,
+          playouts: node.get_playouts(),
+          wins: node.get_wins(),
+          proof: node.get_proof(),
+        })
+      })
+  }
+
+  /// Makes `game` the root of the tree, keeping the subtree reached by the recorded moves when it
+  /// is available, and makes sure the root has children to select from.
+  pub(super) fn initialize(
+    &mut self,
+    game: &Game,
+    random: &mut StdRng,
+    neighborhood: Option<u8>,
+  ) -> Result<(), MctsError> {
+    let reached = self.game.take().and_then(|mut reached| {
+      for square in &self.moves {
+        reached.place_mark(square).ok()?;
+      }
+
+      Some(reached)
+    });
+
+    match self.find_root() {
+      Some(root) if reached.as_ref() == Some(game) => self.reroot(root),
+      _ => {
+        self.nodes.clear();
+        let side = game.get_side_to_move().clone();
+        self.nodes.push(Node::new(side, None, usize::MAX));
+      }
+    }
+
+    self.game = Some(game.clone());
+    self.moves.clear();
+
+    if self.nodes[Self::ROOT_NODE].get_childrens().is_empty() {
+      let mut game = game.clone();
+      let index = self.expand(Self::ROOT_NODE, &mut game, random, neighborhood)?;
+      let outcome = simulate(&mut game, random, neighborhood, &mut Vec::new())?;
+      self.backpropagate(index, &outcome);
+    }
+
+    Ok(())
+  }
+
+  fn uct(mean: f64, playouts: f64, parent_playouts: f64, exploration: f64) -> f64 {
+    mean + exploration * (parent_playouts.ln() / playouts).sqrt()
+  }
+
+  /// Blends the mean result of a node with its all-moves-as-first mean. The weight of the latter
+  /// starts at one and falls as playouts accumulate; `equivalence` is the number of playouts at
+  /// which both means weigh the same.
+  fn rave(node: &Node, playouts: f64, equivalence: f64) -> f64 {
+    let mean = node.get_wins() / playouts;
+
+    if node.get_amaf_playouts() == 0 {
+      return mean;
+    }
+
+    let beta = (equivalence / (3.0 * playouts + equivalence)).sqrt();
+    (1.0 - beta) * mean + beta * node.get_amaf_wins() / node.get_amaf_playouts() as f64
+  }
+
+  /// Returns whether the value of the root has been proven, in which case searching further
+  /// cannot change it.
+  pub(super) fn is_solved(&self) -> bool {
+    self.nodes[Self::ROOT_NODE].get_proof().is_some()
+  }
+
+  /// Descends from the root to a leaf, following the children with the best UCT score, and marks
+  /// the squares on the way in `game`, which starts at the root position. Virtual losses count as
+  /// playouts without a win, and children with a proven value are skipped. When `rave` is set,
+  /// it is the equivalence parameter used to blend in all-moves-as-first means.
+  pub(super) fn select(
+    &self,
+    game: &mut Game,
+    exploration: f64,
+    rave: Option<f64>,
+  ) -> Result<usize, MctsError> {
+    let mut node_index = Self::ROOT_NODE;
+
+    loop {
+      let node = &self.nodes[node_index];
+      let mut best_child = None;
+      let mut best_score = f64::MIN;
+
+      for child in node.get_childrens() {
+        let child_node = &self.nodes[*child];
+        let child_node_visits = child_node.get_visits();
+
+        if child_node.get_proof().is_some() {
+          continue;
+        }
+
+        if child_node_visits == 0 {
+          return self.descend(*child, game);
+        }
+
+        let mean = match rave {
+          Some(equivalence) => Self::rave(child_node, child_node_visits as f64, equivalence),
+          None => child_node.get_wins() / child_node_visits as f64,
+        };
+
+        let score = Self::uct(
+          mean,
+          child_node_visits as f64,
+          node.get_visits() as f64,
+          exploration,
+        );
+
+        if score > best_score {
+          best_score = score;
+          best_child = Some(*child);
+        }
+      }
+
+      match best_child {
+        Some(child) => node_index = self.descend(child, game)?,
+        None => return Ok(node_index),
+      }
+    }
+  }
+
+  /// Marks the square leading to the child `node_index` in `game`.
+  fn descend(&self, node_index: usize, game: &mut Game) -> Result<usize, MctsError> {
+    if let Some(square) = self.nodes[node_index].get_square() {
+      game.place_mark(square)?;
+    }
+
+    Ok(node_index)
+  }
+
+  /// Adds the children of a non-terminal leaf, whose position is `game`, and returns one of the
+  /// added children at random after marking its square in `game`. Every child is checked by
+  /// marking its square and taking it back. Terminal leaves are returned unchanged. With a
+  /// `neighborhood`, only squares that close to a mark become children. A pruned node whose
+  /// children have all been proven is widened instead, adding the squares left out of it.
+  pub(super) fn expand(
+    &mut self,
+    node_index: usize,
+    game: &mut Game,
+    random: &mut StdRng,
+    neighborhood: Option<u8>,
+  ) -> Result<usize, MctsError> {
+    if game.get_outcome().is_some() {
+      return Ok(node_index);
+    }
+
+    let node = &self.nodes[node_index];
+    let added = node.get_childrens().len();
+    let squares = if added == 0 {
+      let squares = get_squares(game, neighborhood);
+      let pruned = neighborhood.is_some() && squares.len() < game.get_empty_squares().len();
+      self.nodes[node_index].set_pruned(pruned);
+      squares
+    } else if node.is_pruned() {
+      let marked: Vec<Square> = node
+        .get_childrens()
+        .iter()
+        .filter_map(|index| *self.nodes[*index].get_square())
+        .collect();
+      self.nodes[node_index].set_pruned(false);
+      game
+        .get_empty_squares()
+        .into_iter()
+        .filter(|square| !marked.contains(square))
+        .collect()
+    } else {
+      return Err(MctsError::NodeAlreadyExpanded);
+    };
+
+    self.nodes[node_index].reserve_childrens(squares.len());
+
+    for square in squares {
+      let children = self.nodes.len();
+      game.place_mark(&square)?;
+      let mut node = Node::new(game.get_side_to_move().clone(), Some(square), node_index);
+
+      match game.get_outcome() {
+        Some(Outcome::Draw) => node.set_proof(Proof::Draw),
+        Some(_) => node.set_proof(Proof::Win),
+        None => (),
+      }
+
+      game.undo_mark()?;
+      self.nodes.push(node);
+      self.nodes[node_index].add_children(children);
+    }
+
+    match self.nodes[node_index].get_childrens()[added..].choose(random) {
+      Some(index) => self.descend(*index, game),
+      None => Err(MctsError::ChildNodesIndexesSliceEmpty),
+    }
+  }
+
+  pub(super) fn backpropagate(&mut self, leaf_index: usize, outcome: &Outcome) {
+    let mut node_index = leaf_index;
+    let mut node = &mut self.nodes[node_index];
+
+    if *outcome == Outcome::Draw {
+      loop {
+        node.add_playout();
+        node.add_draw();
+
+        if node_index == Self::ROOT_NODE {
+          return self.prove(leaf_index);
+        }
+
+        node_index = node.get_parent();
+        node = &mut self.nodes[node_index];
+      }
+    }
+
+    let side = node.get_side();
+
+    let mut add_win = *outcome == Outcome::XWin && *side == Side::O
+      || *outcome == Outcome::OWin && *side == Side::X;
+
+    loop {
+      node.add_playout();
+
+      if add_win {
+        node.add_win();
+      }
+
+      if node_index == Self::ROOT_NODE {
+        return self.prove(leaf_index);
+      }
+
+      node_index = node.get_parent();
+      node = &mut self.nodes[node_index];
+      add_win = !add_win;
+    }
+  }
+
+  /// Returns the proven value of a node that follows from the values of its children, if all of
+  /// them or a winning one have been proven. A winning child anywhere among them proves the loss,
+  /// even when children before it are still unproven.
+  fn get_childrens_proof(&self, node_index: usize) -> Option<Proof> {
+    let mut draw = false;
+    let mut unproven = false;
+
+    for child in self.nodes[node_index].get_childrens() {
+      match self.nodes[*child].get_proof() {
+        Some(Proof::Win) => return Some(Proof::Loss),
+        Some(Proof::Draw) => draw = true,
+        Some(Proof::Loss) => (),
+        None => unproven = true,
+      }
+    }
+
+    match (unproven, draw) {
+      (true, _) => None,
+      (false, true) => Some(Proof::Draw),
+      (false, false) => Some(Proof::Win),
+    }
+  }
+
+  /// Propagates proven values from the leaf of a round towards the root, stopping at the first
+  /// node whose value cannot be proven yet.
+  fn prove(&mut self, mut node_index: usize) {
+    loop {
+      let node = &self.nodes[node_index];
+
+      if node.get_proof().is_none() && !node.get_childrens().is_empty() {
+        match self.get_childrens_proof(node_index) {
+          Some(Proof::Loss) => self.nodes[node_index].set_proof(Proof::Loss),
+          Some(proof) if !node.is_pruned() => self.nodes[node_index].set_proof(proof),
+          _ => return,
+        }
+      }
+
+      if node_index == Self::ROOT_NODE {
+        return;
+      }
+
+      node_index = self.nodes[node_index].get_parent();
+    }
+  }
+
+  /// Updates the all-moves-as-first statistics along the path of a round. A child counts the
+  /// round when its square was marked by the same side anywhere later in the round, either in
+  /// the tree or in `simulation`, the squares marked by the simulation in order. `game` is any
+  /// position of the round.
+  pub(super) fn update_amaf(
+    &mut self,
+    leaf_index: usize,
+    game: &Game,
+    outcome: &Outcome,
+    simulation: &[Square],
+  ) {
+    let mut played = [
+      vec![false; game.get_square_count()],
+      vec![false; game.get_square_count()],
+    ];
+    let mut side = self.nodes[leaf_index].get_side().clone();
+
+    for square in simulation {
+      played[Self::get_side_index(&side)][game.get_square_index(square)] = true;
+      side.switch();
+    }
+
+    let mut node_index = leaf_index;
+
+    loop {
+      let side = self.nodes[node_index].get_side().clone();
+      let result = match (outcome, &side) {
+        (Outcome::Draw, _) => 0.5,
+        (Outcome::XWin, Side::X) | (Outcome::OWin, Side::O) => 1.0,
+        _ => 0.0,
+      };
+
+      for children_index in 0..self.nodes[node_index].get_childrens().len() {
+        let child_index = self.nodes[node_index].get_childrens()[children_index];
+
+        if let Some(square) = self.nodes[child_index].get_square() {
+          let square_index = game.get_square_index(square);
+
+          if played[Self::get_side_index(&side)][square_index] {
+            self.nodes[child_index].add_amaf(result);
+          }
+        }
+      }
+
+      if node_index == Self::ROOT_NODE {
+        return;
+      }
+
+      let node = &self.nodes[node_index];
+
+      if let Some(square) = node.get_square() {
+        played[1 - Self::get_side_index(&side)][game.get_square_index(square)] = true;
+      }
+
+      node_index = node.get_parent();
+    }
+  }
+
+  fn get_side_index(side: &Side) -> usize {
+    match side {
+      Side::X => 0,
+      Side::O => 1,
+    }
+  }
+
+  /// Marks the path from `node_index` to the root as being explored by a thread, which steers
+  /// other threads sharing the tree towards different paths.
+  pub(super) fn add_virtual_loss(&mut self, mut node_index: usize) {
+    loop {
+      self.nodes[node_index].add_virtual_loss();
+
+      if node_index == Self::ROOT_NODE {
+        return;
+      }
+
+      node_index = self.nodes[node_index].get_parent();
+    }
+  }
+
+  pub(super) fn remove_virtual_loss(&mut self, mut node_index: usize) {
+    loop {
+      self.nodes[node_index].remove_virtual_loss();
+
+      if node_index == Self::ROOT_NODE {
+        return;
+      }
+
+      node_index = self.nodes[node_index].get_parent();
+    }
+  }
+
+  /// Follows the moves made since the last search from the root of the tree. Returns the index
+  /// of the node reached, or `None` when a move leads outside of the explored tree.
+  fn find_root(&self) -> Option<usize> {
+    let mut index = Self::ROOT_NODE;
+
+    for square in &self.moves {
+      index = *self
+        .nodes
+        .get(index)?
+        .get_childrens()
+        .iter()
+        .find(|children| self.nodes[**children].get_square().as_ref() == Some(square))?;
+    }
+
+    Some(index)
+  }
+
+  /// Keeps only the subtree under `root` and moves it to the front of the arena, so that `root`
+  /// becomes the root node. Parents are always stored before their children, so the order of the
+  /// remaining nodes is preserved.
+  fn reroot(&mut self, root: usize) {
+    let mut kept = vec![false; self.nodes.len()];
+    let mut stack = vec![root];
+
+    while let Some(index) = stack.pop() {
+      kept[index] = true;
+      stack.extend(self.nodes[index].get_childrens());
+    }
+
+    let mut count = 0;
+    let indexes: Vec<usize> = kept
+      .iter()
+      .map(|keep| {
+        if *keep {
+          count += 1;
+          count - 1
+        } else {
+          usize::MAX
+        }
+      })
+      .collect();
+
+    let mut index = 0;
+    self.nodes.retain(|_| {
+      index += 1;
+      kept[index - 1]
+    });
+
+    for node in &mut self.nodes {
+      node.remap(&indexes);
+    }
+  }
+}
+
+/// Returns the squares to search from `game`, either all the empty squares or, with a
+/// `neighborhood`, only the ones within that many steps of a mark.
+fn get_squares(game: &Game, neighborhood: Option<u8>) -> Vec<Square> {
+  match neighborhood {
+    Some(distance) => game.get_candidate_squares(distance),
+    None => game.get_empty_squares(),
+  }
+}
+
+/// Plays random moves in `game` until the game is over, recording them in `simulation`.
+pub(super) fn simulate(
+  game: &mut Game,
+  random: &mut StdRng,
+  neighborhood: Option<u8>,
+  simulation: &mut Vec<Square>,
+) -> Result<Outcome, MctsError> {
+  loop {
+    if let Some(outcome) = game.get_outcome() {
+      return Ok(outcome.clone());
+    }
+
+    let square = match get_squares(game, neighborhood).choose(random) {
+      Some(square) => *square,
+      None => Err(MctsError::NoSquaresAvailable)?,
+    };
+
+    game.place_mark(&square)?;
+    simulation.push(square);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{super::Mcts, Proof, Tree};
+  use crate::{
+    game::{Game, Square, Variant},
+    player::MctsConfig,
+  };
+  use rand::{rngs::StdRng, SeedableRng};
+
+  fn get_config() -> MctsConfig {
+    MctsConfig::new(
+      MctsConfig::ROUNDS_DEFAULT,
+      MctsConfig::EXPLORATION_DEFAULT,
+      None,
+      MctsConfig::NODES_CAPACITY_DEFAULT,
+    )
+  }
+
+  /// Searches `game` from a new tree for `rounds` rounds, with the RAVE equivalence `rave`.
+  fn search(game: &Game, rounds: usize, rave: Option<f64>, random: &mut StdRng) -> Tree {
+    let config = get_config().with_rave(rave);
+    let mut tree = Tree::new(0);
+    tree.initialize(game, random, None).unwrap();
+
+    for _ in 0..rounds {
+      Mcts::round(&mut tree, random, &config, game).unwrap();
+    }
+
+    tree
+  }
+
+  fn get_root_playouts(tree: &Tree) -> u32 {
+    tree
+      .get_root_statistics()
+      .map(|statistics| statistics.playouts)
+      .sum()
+  }
+
+  #[test]
+  fn searches_keep_the_subtree_of_the_moves_played() {
+    let game: Game = "____/____/____/____ x 3".parse().unwrap();
+    let mut random = StdRng::seed_from_u64(1);
+    let mut tree = search(&game, 2000, None, &mut random);
+    let best = tree
+      .get_root_statistics()
+      .max_by_key(|statistics| statistics.playouts)
+      .unwrap();
+
+    let mut next = game.clone();
+    next.place_mark(&best.square).unwrap();
+    tree.add_move(&best.square);
+    tree.initialize(&next, &mut random, None).unwrap();
+    assert!(best.playouts > 100);
+    assert!(get_root_playouts(&tree) + 1 >= best.playouts);
+  }
+
+  #[test]
+  fn searches_start_over_from_positions_the_moves_do_not_reach() {
+    let game: Game = "____/____/____/____ x 3".parse().unwrap();
+    let mut random = StdRng::seed_from_u64(2);
+    let mut tree = search(&game, 500, None, &mut random);
+
+    let mut next = game.clone();
+    next.place_mark(&Square::new(0, 0)).unwrap();
+    tree.add_move(&Square::new(1, 1));
+    tree.initialize(&next, &mut random, None).unwrap();
+    assert_eq!(get_root_playouts(&tree), 1);
+
+    let mut tree = search(&game, 500, None, &mut random);
+    tree.undo_move();
+    tree.initialize(&game, &mut random, None).unwrap();
+    assert_eq!(get_root_playouts(&tree), 1);
+  }
+
+  #[test]
+  fn rave_searches_count_the_moves_played_later_in_playouts() {
+    let game: Game = "____/____/____/____ x 3".parse().unwrap();
+    let mut random = StdRng::seed_from_u64(3);
+    let amaf_playouts = |tree: &Tree| -> u32 {
+      tree.nodes[Tree::ROOT_NODE]
+        .get_childrens()
+        .iter()
+        .map(|index| tree.nodes[*index].get_amaf_playouts())
+        .sum()
+    };
+
+    let tree = search(&game, 500, Some(100.0), &mut random);
+    assert!(amaf_playouts(&tree) > get_root_playouts(&tree));
+
+    let tree = search(&game, 500, None, &mut random);
+    assert_eq!(amaf_playouts(&tree), 0);
+  }
+
+  #[test]
+  fn moves_allowing_a_winning_reply_are_proven_to_lose() {
+    let game: Game = "xo_/_o_/__x x".parse().unwrap();
+    let config = get_config();
+    let mut tree = Tree::new(0);
+    let mut random = StdRng::seed_from_u64(0);
+    tree.initialize(&game, &mut random, None).unwrap();
+
+    for _ in 0..10 {
+      Mcts::round(&mut tree, &mut random, &config, &game).unwrap();
+    }
+
+    let block: Square = "b1".parse().unwrap();
+
+    for statistics in tree.get_root_statistics() {
+      if statistics.square != block {
+        assert!(
+          statistics.proof == Some(Proof::Loss),
+          "{} is not proven to lose",
+          statistics.square
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn pruned_nodes_with_proven_children_are_widened_instead_of_expanded_again() {
+    let mut game = Game::new(Variant::GOMOKU);
+
+    for square in ["a1", "f8", "c1", "g8", "e1", "h8", "m1", "i8"] {
+      game.place_mark(&square.parse().unwrap()).unwrap();
+    }
+
+    let config = get_config().with_neighborhood(Some(1));
+    let mut tree = Tree::new(0);
+    let mut random = StdRng::seed_from_u64(4);
+    tree.initialize(&game, &mut random, Some(1)).unwrap();
+
+    for _ in 0..3000 {
+      Mcts::round(&mut tree, &mut random, &config, &game).unwrap();
+    }
+
+    let childrens = tree.nodes[Tree::ROOT_NODE].get_childrens().len();
+    assert_eq!(childrens, game.get_empty_squares().len());
+    assert!(tree.is_solved());
+    assert!(tree
+      .get_root_statistics()
+      .all(|statistics| statistics.proof == Some(Proof::Loss)));
+  }
+}
@@ -12,123 +12,228 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::{
+  registry::Entry,
+  transposition::{Bound, TranspositionTable},
+  Clock, Player, PlayerError,
+};
 use crate::game::{Game, GameError, Outcome, Side, Square};
 use std::cmp;
 use thiserror::Error;
 
+/// Errors of the [`Minimax`] player.
 #[derive(Error, Debug)]
-pub(crate) enum MinimaxError {
+pub enum MinimaxError {
+  /// A move could not be placed or taken back during the search.
   #[error(transparent)]
   Game(#[from] GameError),
+  /// The search reached a position without empty squares.
   #[error("no empty squares are available on the current node")]
   NoEmptySquares,
 }
 
-type Depth = u8;
-type Value = u8;
+type Value = u16;
 
-const X_WIN: Value = 32;
+const X_WIN: Value = 1024;
 const DRAW: Value = X_WIN / 2;
 const O_WIN: Value = 0;
 
-pub(super) fn get_move(game: &Game) -> Result<Square, MinimaxError> {
-  if *game.get_side_to_move() == Side::X {
-    get_best_square_max(game)
-  } else {
-    get_best_square_min(game)
-  }
+pub(super) const ENTRY: Entry = Entry {
+  name: "minimax",
+  options: &[],
+  interactive: false,
+  create: |_, _| Ok(Box::new(Minimax::new())),
+};
+
+/// Value of a move for the side making it, with the number of marks placed by both sides,
+/// including the move itself, until the game ends when both sides play perfectly.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Evaluation {
+  /// The side wins after the given number of marks.
+  Win(u8),
+  /// Neither side wins.
+  Draw,
+  /// The side loses after the given number of marks.
+  Loss(u8),
 }
 
-fn get_value(outcome: &Outcome, depth: &Depth) -> Value {
-  match outcome {
-    Outcome::Draw => DRAW,
-    Outcome::XWin => X_WIN - depth,
-    Outcome::OWin => O_WIN + depth,
+impl Evaluation {
+  /// Orders evaluations from the worst to the best: faster wins and slower losses are better.
+  pub fn get_rank(&self) -> i16 {
+    match self {
+      Self::Win(plies) => i16::MAX - *plies as i16,
+      Self::Draw => 0,
+      Self::Loss(plies) => i16::MIN + *plies as i16,
+    }
   }
 }
 
-fn max(node: &Game, depth: &Depth) -> Result<Value, MinimaxError> {
-  let mut value = O_WIN;
-
-  for square in node.get_empty_squares() {
-    let mut game = node.clone();
-    game.place_mark(&square)?;
-    value = cmp::max(
-      match game.get_outcome() {
-        Some(outcome) => get_value(outcome, depth),
-        None => min(&game, &(depth + 1))?,
-      },
-      value,
-    );
-  }
-
-  Ok(value)
+/// Plays perfectly with a plain minimax search, remembering the values of the positions it has
+/// searched.
+pub struct Minimax {
+  transposition_table: TranspositionTable<Value>,
+  visited_nodes: u64,
 }
 
-fn min(node: &Game, depth: &Depth) -> Result<Value, MinimaxError> {
-  let mut value = X_WIN;
-
-  for square in node.get_empty_squares() {
-    let mut game = node.clone();
-    game.place_mark(&square)?;
-    value = cmp::min(
-      match game.get_outcome() {
-        Some(outcome) => get_value(outcome, depth),
-        None => max(&game, &(depth + 1))?,
-      },
-      value,
-    );
+impl Minimax {
+  /// Creates a player with an empty table of searched positions.
+  pub fn new() -> Self {
+    Self {
+      transposition_table: TranspositionTable::new(),
+      visited_nodes: 0,
+    }
   }
 
-  Ok(value)
-}
+  /// Evaluates every square the side to move can mark. Faster wins and slower losses are
+  /// preferred, so the distances are the ones of perfect play.
+  pub fn evaluate(&mut self, game: &Game) -> Result<Vec<(Square, Evaluation)>, MinimaxError> {
+    let mut node = game.clone();
+    let marks = game.get_marks_count() as Value;
+    let x_to_move = *game.get_side_to_move() == Side::X;
+
+    game
+      .get_empty_squares()
+      .into_iter()
+      .map(|square| {
+        let value = self.get_child_value(&mut node, &square)?;
+        let evaluation = match value {
+          DRAW => Evaluation::Draw,
+          value if (value > DRAW) == x_to_move => Evaluation::Win(Self::get_plies(value, marks)),
+          value => Evaluation::Loss(Self::get_plies(value, marks)),
+        };
+        Ok((square, evaluation))
+      })
+      .collect()
+  }
 
-fn get_best_square_max(game: &Game) -> Result<Square, MinimaxError> {
-  let mut best_square = None;
-  let mut best_value = O_WIN;
+  /// Values depend on the number of marks on the grid rather than on the distance from the
+  /// searched position, so they can be reused from the transposition table in any later search.
+  fn get_value(game: &Game, outcome: &Outcome) -> Value {
+    match outcome {
+      Outcome::Draw => DRAW,
+      Outcome::XWin => X_WIN - game.get_marks_count() as Value,
+      Outcome::OWin => O_WIN + game.get_marks_count() as Value,
+    }
+  }
 
-  for square in game.get_empty_squares() {
-    let mut node = game.clone();
-    node.place_mark(&square)?;
+  /// Returns the number of marks placed between a position with `marks` marks and the end of the
+  /// game valued `value`.
+  fn get_plies(value: Value, marks: Value) -> u8 {
+    let end = if value > DRAW {
+      X_WIN - value
+    } else {
+      value - O_WIN
+    };
+    (end - marks) as u8
+  }
+
+  /// Returns the value of the position reached by placing a mark on `square`. The mark is taken
+  /// back afterwards, so the whole search works on a single game.
+  fn get_child_value(&mut self, node: &mut Game, square: &Square) -> Result<Value, MinimaxError> {
+    node.place_mark(square)?;
+    self.visited_nodes += 1;
 
     let value = match node.get_outcome() {
-      Some(outcome) => get_value(outcome, &0),
-      None => min(&node, &1)?,
+      Some(outcome) => Self::get_value(node, outcome),
+      None if *node.get_side_to_move() == Side::X => self.max(node)?,
+      None => self.min(node)?,
     };
 
-    if value > best_value {
-      best_value = value;
-      best_square = Some(square);
+    node.undo_mark()?;
+    Ok(value)
+  }
+
+  fn max(&mut self, node: &mut Game) -> Result<Value, MinimaxError> {
+    if let Some(entry) = self.transposition_table.get(node) {
+      return Ok(entry.get_value());
     }
+
+    let mut value = O_WIN;
+
+    for square in node.get_empty_squares() {
+      value = cmp::max(self.get_child_value(node, &square)?, value);
+    }
+
+    self.transposition_table.insert(node, value, Bound::Exact);
+    Ok(value)
   }
 
-  match best_square {
-    Some(square) => Ok(square),
-    None => Err(MinimaxError::NoEmptySquares),
+  fn min(&mut self, node: &mut Game) -> Result<Value, MinimaxError> {
+    if let Some(entry) = self.transposition_table.get(node) {
+      return Ok(entry.get_value());
+    }
+
+    let mut value = X_WIN;
+
+    for square in node.get_empty_squares() {
+      value = cmp::min(self.get_child_value(node, &square)?, value);
+    }
+
+    self.transposition_table.insert(node, value, Bound::Exact);
+    Ok(value)
   }
-}
 
-fn get_best_square_min(game: &Game) -> Result<Square, MinimaxError> {
-  let mut best_square = None;
-  let mut best_value = X_WIN;
+  fn get_best_square_max(&mut self, game: &Game) -> Result<Square, MinimaxError> {
+    let mut best_square = None;
+    let mut best_value = O_WIN;
+    let mut node = game.clone();
+
+    for square in game.get_empty_squares() {
+      let value = self.get_child_value(&mut node, &square)?;
 
-  for square in game.get_empty_squares() {
+      if value > best_value {
+        best_value = value;
+        best_square = Some(square);
+      }
+    }
+
+    match best_square {
+      Some(square) => Ok(square),
+      None => Err(MinimaxError::NoEmptySquares),
+    }
+  }
+
+  fn get_best_square_min(&mut self, game: &Game) -> Result<Square, MinimaxError> {
+    let mut best_square = None;
+    let mut best_value = X_WIN;
     let mut node = game.clone();
-    node.place_mark(&square)?;
 
-    let value = match node.get_outcome() {
-      Some(outcome) => get_value(outcome, &0),
-      None => max(&node, &1)?,
-    };
+    for square in game.get_empty_squares() {
+      let value = self.get_child_value(&mut node, &square)?;
+
+      if value < best_value {
+        best_value = value;
+        best_square = Some(square);
+      }
+    }
 
-    if value < best_value {
-      best_value = value;
-      best_square = Some(square);
+    match best_square {
+      Some(square) => Ok(square),
+      None => Err(MinimaxError::NoEmptySquares),
     }
   }
+}
+
+impl Default for Minimax {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Player for Minimax {
+  fn get_name(&self) -> &str {
+    "minimax"
+  }
+
+  fn get_move(&mut self, game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    Ok(if *game.get_side_to_move() == Side::X {
+      self.get_best_square_max(game)?
+    } else {
+      self.get_best_square_min(game)?
+    })
+  }
 
-  match best_square {
-    Some(square) => Ok(square),
-    None => Err(MinimaxError::NoEmptySquares),
+  fn get_visited_nodes(&self) -> Option<u64> {
+    Some(self.visited_nodes)
   }
 }
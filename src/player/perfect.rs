@@ -0,0 +1,96 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{registry::Entry, Clock, Player, PlayerError};
+use crate::{
+  game::{Game, Square},
+  solver::{SolverError, Table},
+};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use thiserror::Error;
+
+/// Errors of the [`Perfect`] player.
+#[derive(Error, Debug)]
+pub enum PerfectError {
+  /// The table could not be solved.
+  #[error(transparent)]
+  Solver(#[from] SolverError),
+  /// The table is solved for the first variant, but the game is played on the second.
+  #[error("the table is solved for {0}, not for {1}")]
+  VariantMismatch(String, String),
+  /// The position does not occur in the table.
+  #[error("the position is not in the table")]
+  UnknownPosition,
+}
+
+/// Plays one of the best moves of the table given with --table, chosen at random. Without one,
+/// the variant of the first game is solved when the first move is asked for.
+pub(super) const ENTRY: Entry = Entry {
+  name: "perfect",
+  options: &[],
+  interactive: false,
+  create: |_, context| Ok(Box::new(Perfect::new(context.get_table().cloned()))),
+};
+
+/// Plays a random one of the best moves of a solved table.
+pub struct Perfect {
+  table: Option<Table>,
+  random: StdRng,
+}
+
+impl Perfect {
+  /// Without a `table`, solves the variant of the first game it plays.
+  pub fn new(table: Option<Table>) -> Self {
+    Self {
+      table,
+      random: StdRng::from_entropy(),
+    }
+  }
+
+  fn get_best_square(&mut self, game: &Game) -> Result<Square, PerfectError> {
+    let table = match &mut self.table {
+      Some(table) => table,
+      table @ None => table.insert(Table::solve(*game.get_variant())?),
+    };
+
+    if table.get_variant() != game.get_variant() {
+      return Err(PerfectError::VariantMismatch(
+        table.get_variant().to_string(),
+        game.get_variant().to_string(),
+      ));
+    }
+
+    let solution = table.get(game).ok_or(PerfectError::UnknownPosition)?;
+
+    solution
+      .get_best_squares(game)
+      .choose(&mut self.random)
+      .copied()
+      .ok_or(PerfectError::UnknownPosition)
+  }
+}
+
+impl Player for Perfect {
+  fn get_name(&self) -> &str {
+    "perfect"
+  }
+
+  fn get_move(&mut self, game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    Ok(self.get_best_square(game)?)
+  }
+
+  fn new_game(&mut self, seed: u64) {
+    self.random = StdRng::seed_from_u64(seed);
+  }
+}
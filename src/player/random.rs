@@ -12,31 +12,59 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::{registry::Entry, Clock, Player, PlayerError};
 use crate::game::{Game, Square};
-use rand::{rngs::ThreadRng, seq::SliceRandom};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use thiserror::Error;
 
+/// Errors of the [`Random`] player.
 #[derive(Error, Debug)]
-pub(crate) enum RandomError {
+pub enum RandomError {
+  /// The position has no empty squares.
   #[error("unable to choose a move because there are no empty squares")]
   UnableToChooseMove,
 }
 
-pub(crate) struct Random {
-  random: ThreadRng,
+pub(super) const ENTRY: Entry = Entry {
+  name: "random",
+  options: &[],
+  interactive: false,
+  create: |_, _| Ok(Box::new(Random::new())),
+};
+
+/// Marks a random empty square.
+pub struct Random {
+  random: StdRng,
 }
 
 impl Random {
-  pub(super) fn new() -> Self {
+  /// Creates a player seeded from the system's entropy until a game gives it a seed.
+  pub fn new() -> Self {
     Self {
-      random: rand::thread_rng(),
+      random: StdRng::from_entropy(),
     }
   }
+}
+
+impl Default for Random {
+  fn default() -> Self {
+    Self::new()
+  }
+}
 
-  pub(super) fn get_move(&mut self, game: &Game) -> Result<Square, RandomError> {
+impl Player for Random {
+  fn get_name(&self) -> &str {
+    "random"
+  }
+
+  fn get_move(&mut self, game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
     match game.get_empty_squares().choose(&mut self.random) {
       Some(square) => Ok(*square),
-      None => Err(RandomError::UnableToChooseMove),
+      None => Err(RandomError::UnableToChooseMove.into()),
     }
   }
+
+  fn new_game(&mut self, seed: u64) {
+    self.random = StdRng::seed_from_u64(seed);
+  }
 }
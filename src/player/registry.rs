@@ -0,0 +1,258 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{MctsConfig, Player, PlayerError, ENTRIES};
+use crate::solver::Table;
+
+/// Type of the value of a player option.
+pub(crate) enum OptionType {
+  /// A whole number from the first bound to the second one.
+  Integer(u64, u64),
+  /// A finite real number from the first bound to the second one.
+  Number(f64, f64),
+  /// One of the given words.
+  Choice(&'static [&'static str]),
+  /// Any text without commas.
+  Text,
+}
+
+impl OptionType {
+  fn parse(&self, text: &str) -> Option<OptionValue> {
+    match self {
+      Self::Integer(minimum, maximum) => text
+        .parse()
+        .ok()
+        .filter(|value| (minimum..=maximum).contains(&value))
+        .map(OptionValue::Integer),
+      Self::Number(minimum, maximum) => text
+        .parse()
+        .ok()
+        .filter(|value: &f64| value.is_finite() && (minimum..=maximum).contains(&value))
+        .map(OptionValue::Number),
+      Self::Choice(words) => words
+        .iter()
+        .find(|word| word.eq_ignore_ascii_case(text))
+        .map(|word| OptionValue::Text(word.to_string())),
+      Self::Text => Some(OptionValue::Text(text.to_string())),
+    }
+  }
+}
+
+impl std::fmt::Display for OptionType {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      Self::Integer(minimum, maximum) => {
+        write!(f, "a whole number from {} to {}", minimum, maximum)
+      }
+      Self::Number(minimum, maximum) if *maximum == f64::MAX => {
+        write!(f, "a number of at least {}", minimum)
+      }
+      Self::Number(minimum, maximum) => write!(f, "a number from {} to {}", minimum, maximum),
+      Self::Choice(words) => write!(f, "one of {}", words.join(", ")),
+      Self::Text => write!(f, "text"),
+    }
+  }
+}
+
+/// An option taken by the players of a type.
+pub(crate) struct OptionSpec {
+  pub(super) name: &'static str,
+  pub(super) option_type: OptionType,
+}
+
+#[derive(Clone)]
+enum OptionValue {
+  Integer(u64),
+  Number(f64),
+  Text(String),
+}
+
+/// The options given to a player, checked against the types of its options. An option given
+/// more than once takes the last value.
+#[derive(Clone, Default)]
+pub(crate) struct Options {
+  values: Vec<(&'static str, OptionValue)>,
+}
+
+impl Options {
+  pub(crate) fn get_integer(&self, name: &str) -> Option<u64> {
+    match self.get(name) {
+      Some(OptionValue::Integer(value)) => Some(*value),
+      _ => None,
+    }
+  }
+
+  pub(crate) fn get_number(&self, name: &str) -> Option<f64> {
+    match self.get(name) {
+      Some(OptionValue::Number(value)) => Some(*value),
+      _ => None,
+    }
+  }
+
+  pub(crate) fn get_text(&self, name: &str) -> Option<&str> {
+    match self.get(name) {
+      Some(OptionValue::Text(value)) => Some(value),
+      _ => None,
+    }
+  }
+
+  fn get(&self, name: &str) -> Option<&OptionValue> {
+    self
+      .values
+      .iter()
+      .rev()
+      .find(|(other, _)| *other == name)
+      .map(|(_, value)| value)
+  }
+}
+
+/// Settings given on the command line for all players rather than with each of them.
+#[derive(Clone)]
+pub struct Context {
+  mcts_config: MctsConfig,
+  table: Option<Table>,
+  seeded: bool,
+}
+
+impl Context {
+  /// `mcts_config` is the search of 'mcts' players before their options and `table` the one
+  /// 'perfect' players play from, if any.
+  pub fn new(mcts_config: MctsConfig, table: Option<Table>) -> Self {
+    Self {
+      mcts_config,
+      table,
+      seeded: false,
+    }
+  }
+
+  /// Makes player types whose settings cannot replay a game from its seed refuse them, for
+  /// matches given a seed to be reproduced.
+  pub fn with_seeded(mut self, seeded: bool) -> Self {
+    self.seeded = seeded;
+    self
+  }
+
+  /// Returns whether the match is to be replayed from its seed.
+  pub fn is_seeded(&self) -> bool {
+    self.seeded
+  }
+
+  /// Returns the settings of mcts players that are not given as options.
+  pub fn get_mcts_config(&self) -> &MctsConfig {
+    &self.mcts_config
+  }
+
+  /// Returns the solved table perfect players look positions up in, if any.
+  pub fn get_table(&self) -> Option<&Table> {
+    self.table.as_ref()
+  }
+}
+
+/// Makes a player of a type from its options.
+pub(super) type Constructor = fn(&Options, &Context) -> Result<Box<dyn Player>, PlayerError>;
+
+/// A player type: its name on the command line, the options it takes and the way its players
+/// are made. Players that wait for a person are `interactive`.
+pub(crate) struct Entry {
+  pub(super) name: &'static str,
+  pub(super) options: &'static [OptionSpec],
+  pub(super) interactive: bool,
+  pub(super) create: Constructor,
+}
+
+/// Returns the names of all player types.
+fn get_names() -> String {
+  ENTRIES
+    .iter()
+    .map(|entry| entry.name)
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// A player type with options, written as the type optionally followed by a colon and
+/// comma-separated `name=value` pairs, such as `mcts:rounds=1000,c=1.0`. The options of 'mcts'
+/// players are named like their command-line arguments without the `mcts-` prefix.
+#[derive(Clone)]
+pub struct PlayerSpec {
+  text: String,
+  entry: &'static Entry,
+  options: Options,
+}
+
+impl PlayerSpec {
+  /// Returns whether the players of this type wait for a person to choose their moves.
+  pub fn is_interactive(&self) -> bool {
+    self.entry.interactive
+  }
+
+  /// Returns a new player made with the options on top of `context`.
+  pub fn get_player(&self, context: &Context) -> Result<Box<dyn Player>, PlayerError> {
+    (self.entry.create)(&self.options, context)
+  }
+}
+
+impl std::str::FromStr for PlayerSpec {
+  type Err = PlayerError;
+
+  fn from_str(text: &str) -> Result<Self, Self::Err> {
+    let (name, options) = match text.split_once(':') {
+      Some((name, options)) => (name, Some(options)),
+      None => (text, None),
+    };
+    let entry = ENTRIES
+      .iter()
+      .find(|entry| entry.name.eq_ignore_ascii_case(name))
+      .ok_or_else(|| PlayerError::UnknownPlayerType(name.to_string(), get_names()))?;
+    let mut values = Vec::new();
+
+    for option in options.into_iter().flat_map(|options| options.split(',')) {
+      let (option, value) = option
+        .split_once('=')
+        .ok_or_else(|| PlayerError::InvalidOption(option.to_string()))?;
+
+      if entry.options.is_empty() {
+        return Err(PlayerError::UnexpectedOptions(entry.name.to_string()));
+      }
+
+      let spec = entry
+        .options
+        .iter()
+        .find(|spec| spec.name == option)
+        .ok_or_else(|| {
+          let names: Vec<&str> = entry.options.iter().map(|spec| spec.name).collect();
+          PlayerError::UnknownOption(option.to_string(), names.join(", "))
+        })?;
+      let value = spec.option_type.parse(value).ok_or_else(|| {
+        PlayerError::InvalidOptionValue(
+          value.to_string(),
+          option.to_string(),
+          spec.option_type.to_string(),
+        )
+      })?;
+      values.push((spec.name, value));
+    }
+
+    Ok(Self {
+      text: text.to_string(),
+      entry,
+      options: Options { values },
+    })
+  }
+}
+
+impl std::fmt::Display for PlayerSpec {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{}", self.text)
+  }
+}
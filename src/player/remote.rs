@@ -0,0 +1,128 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+  connection::Connection,
+  registry::{Entry, OptionSpec, OptionType},
+  Action, Clock, Player, PlayerError,
+};
+use crate::game::{Game, Square};
+use std::{
+  io,
+  net::{TcpListener, ToSocketAddrs},
+};
+use thiserror::Error;
+
+/// Errors of the [`Remote`] player.
+#[derive(Error, Debug)]
+pub enum RemoteError {
+  /// Waiting for the other side to connect failed.
+  #[error(transparent)]
+  Io(#[from] io::Error),
+  /// No address to wait at was given.
+  #[error("remote players need an address to wait at, such as 'remote:host=127.0.0.1:7878'")]
+  MissingAddress,
+}
+
+pub(super) const ENTRY: Entry = Entry {
+  name: "remote",
+  options: &[OptionSpec {
+    name: "host",
+    option_type: OptionType::Text,
+  }],
+  interactive: false,
+  create: |options, _| {
+    let address = options
+      .get_text("host")
+      .ok_or(RemoteError::MissingAddress)?;
+    Ok(Box::new(Remote::host(address)?))
+  },
+};
+
+/// Plays the moves of a person or an engine on another computer, which joins over TCP and
+/// speaks the protocol of the [`engine`](crate::engine) module, such as this program started
+/// with `--join`.
+pub struct Remote {
+  connection: Connection,
+  game: Option<Game>,
+}
+
+impl Remote {
+  /// Waits for a player to join at `address` and for it to introduce itself.
+  pub fn host(address: impl ToSocketAddrs) -> Result<Self, PlayerError> {
+    Self::new(&TcpListener::bind(address).map_err(RemoteError::from)?)
+  }
+
+  /// Waits for a player to join through `listener` and for it to introduce itself.
+  pub fn new(listener: &TcpListener) -> Result<Self, PlayerError> {
+    let (stream, _) = listener.accept().map_err(RemoteError::from)?;
+    let input = stream.try_clone().map_err(RemoteError::from)?;
+
+    Ok(Self {
+      connection: Connection::new("remote", input, stream)?,
+      game: None,
+    })
+  }
+}
+
+impl Player for Remote {
+  fn get_name(&self) -> &str {
+    self.connection.get_name()
+  }
+
+  fn get_move(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    self.game = Some(game.clone());
+    Ok(self.connection.get_move(game, clock)?)
+  }
+
+  /// Blocks until the remote player moves or resigns, or with a `clock`, until the time for the
+  /// move has run out.
+  fn get_action(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Action, PlayerError> {
+    self.game = Some(game.clone());
+    Ok(self.connection.get_action(game, clock)?)
+  }
+
+  fn new_game(&mut self, seed: u64) {
+    self.game = None;
+    let _ = self.connection.send(&format!("newgame {}", seed));
+  }
+
+  /// Follows the game once the remote player has been asked for a move, and shows it the final
+  /// position when a mark ends the game.
+  fn notify_move(&mut self, square: &Square) {
+    if let Some(game) = &mut self.game {
+      if game.place_mark(square).is_ok() && game.get_outcome().is_some() {
+        let game = game.clone();
+        let _ = self.connection.send_position(&game);
+      }
+    }
+  }
+
+  fn notify_undo(&mut self) {
+    if let Some(game) = &mut self.game {
+      let _ = game.undo_mark();
+    }
+  }
+
+  fn is_external(&self) -> bool {
+    true
+  }
+}
+
+/// Tells the remote player that the match is over.
+impl Drop for Remote {
+  fn drop(&mut self) {
+    let _ = self.connection.send("quit");
+  }
+}
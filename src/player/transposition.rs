@@ -0,0 +1,69 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::game::{Game, Key};
+use std::collections::HashMap;
+
+/// Describes how a stored value relates to the true value of a position.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum Bound {
+  /// The stored value is the true value.
+  Exact,
+  /// The true value is greater than or equal to the stored value.
+  Lower,
+  /// The true value is less than or equal to the stored value.
+  Upper,
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct Entry<V> {
+  value: V,
+  bound: Bound,
+}
+
+impl<V: Copy> Entry<V> {
+  pub(super) fn get_value(&self) -> V {
+    self.value
+  }
+
+  pub(super) fn get_bound(&self) -> Bound {
+    self.bound
+  }
+}
+
+/// Caches search results by position. Positions that are rotations or reflections of one another
+/// share an entry, so values stored here must not depend on where the marks are, only on how
+/// they are arranged.
+pub(super) struct TranspositionTable<V> {
+  entries: HashMap<Key, Entry<V>>,
+}
+
+impl<V: Copy> TranspositionTable<V> {
+  pub(super) fn new() -> Self {
+    const ENTRIES_CAPACITY: usize = 8192;
+    Self {
+      entries: HashMap::with_capacity(ENTRIES_CAPACITY),
+    }
+  }
+
+  pub(super) fn get(&self, game: &Game) -> Option<Entry<V>> {
+    self.entries.get(&game.get_canonical_key()).copied()
+  }
+
+  pub(super) fn insert(&mut self, game: &Game, value: V, bound: Bound) {
+    self
+      .entries
+      .insert(game.get_canonical_key(), Entry { value, bound });
+  }
+}
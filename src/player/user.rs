@@ -12,67 +12,195 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::{registry::Entry, Action, Clock, Player, PlayerError};
 use crate::game::{Game, Square};
-use std::{collections::HashSet, io};
+use std::{
+  io,
+  sync::mpsc::{self, Receiver, RecvTimeoutError},
+  thread,
+  time::{Duration, Instant},
+};
 use thiserror::Error;
 
+/// Errors of the [`User`] player.
 #[derive(Error, Debug)]
-pub(crate) enum UserError {
+pub enum UserError {
+  /// Reading the input failed.
   #[error(transparent)]
   Io(#[from] io::Error),
+  /// The input ended before a move was given.
+  #[error("the input has been closed")]
+  InputClosed,
+  /// No move was given before the deadline.
+  #[error("the time for the move has run out")]
+  OutOfTime,
+  /// The controller asked for the move at once.
+  #[error("the move has been stopped")]
+  Stopped,
+  /// The user took back a move or placed one again where only a move can be made.
+  #[error("moves cannot be taken back here")]
+  TakeBackRefused,
+  /// The user resigned where only a move can be made.
+  #[error("games cannot be resigned here")]
+  ResignationRefused,
 }
 
-pub(crate) struct User {
-  empty_squares: HashSet<Square>,
-  input: String,
+pub(super) const ENTRY: Entry = Entry {
+  name: "user",
+  options: &[],
+  interactive: true,
+  create: |_, _| Ok(Box::new(User::new())),
+};
+
+/// Asks the person at the keyboard for moves on the standard input.
+pub struct User {
+  lines: Option<Receiver<io::Result<String>>>,
 }
 
 impl User {
-  pub(super) fn new() -> Self {
-    const INPUT_CAPACITY: usize = 2;
-    Self {
-      empty_squares: HashSet::with_capacity(Square::COUNT as usize),
-      input: String::with_capacity(INPUT_CAPACITY),
-    }
+  /// How often a user waiting for input checks whether the move has been stopped.
+  const POLL_TIME: Duration = Duration::from_millis(100);
+
+  /// Creates a player reading the standard input.
+  pub fn new() -> Self {
+    Self { lines: None }
   }
 
-  pub(super) fn get_move(&mut self, game: &Game) -> Result<Square, UserError> {
-    self.empty_squares.clear();
-    self.empty_squares.extend(game.get_empty_squares());
+  /// Reads squares written as a column letter and a row number, such as `b2`. On the classic
+  /// 3x3 board squares can also be picked with the numeric keypad, 1 being the bottom left one.
+  /// `u` takes back the last move, `r` places it again and `resign` gives up the game.
+  fn get_input(
+    &mut self,
+    game: &Game,
+    clock: Option<&Clock>,
+    deadline: Option<Instant>,
+  ) -> Result<Action, UserError> {
+    let variant = game.get_variant();
+    let numpad = variant.get_width() == 3 && variant.get_height() == 3;
 
     loop {
-      let square = self.get_input()?;
+      let line = self.read_line(clock, deadline)?;
+      let input = line.trim();
+
+      match input {
+        "u" => return Ok(Action::Undo),
+        "r" => return Ok(Action::Redo),
+        "resign" => return Ok(Action::Resign),
+        _ => (),
+      }
 
-      if self.empty_squares.contains(&square) {
-        return Ok(square);
+      match input.parse::<u8>() {
+        Ok(number) if numpad => match number {
+          1..=9 => {
+            return Ok(Action::Mark(Square::new(
+              (number - 1) % 3,
+              (number - 1) / 3,
+            )))
+          }
+          _ => eprintln!("number entered is not within the acceptable range"),
+        },
+        _ => match input.parse::<Square>() {
+          Ok(square) if game.is_on_board(&square) => return Ok(Action::Mark(square)),
+          Ok(square) => eprintln!("{} square is not on the board", square),
+          Err(error) => eprintln!("{}", error),
+        },
       }
 
-      println!("{} square is not empty", square)
+      if numpad {
+        eprintln!("please try again with a number between 1 and 9, or u to take back a move");
+      } else {
+        eprintln!("please try again with a square such as b2, or u to take back a move");
+      }
     }
   }
 
-  fn get_input(&mut self) -> Result<Square, UserError> {
+  /// Reads the next line of the standard input, waiting at most until `deadline` or until the
+  /// `clock` is stopped. The input is read on a thread of its own, so that a move can run out of
+  /// time while it waits for a line.
+  fn read_line(
+    &mut self,
+    clock: Option<&Clock>,
+    deadline: Option<Instant>,
+  ) -> Result<String, UserError> {
+    let lines = self.lines.get_or_insert_with(|| {
+      let (sender, receiver) = mpsc::channel();
+      thread::spawn(move || loop {
+        let mut line = String::new();
+        let result = io::stdin().read_line(&mut line);
+        let end = matches!(result, Ok(0) | Err(_));
+
+        if sender.send(result.map(|_| line)).is_err() || end {
+          break;
+        }
+      });
+      receiver
+    });
+
     loop {
-      self.input.clear();
-      io::stdin().read_line(&mut self.input)?;
-
-      match self.input.trim().parse::<u8>() {
-        Ok(number) => match number {
-          1 => return Ok(Square::BottomLeft),
-          2 => return Ok(Square::BottomMiddle),
-          3 => return Ok(Square::BottomRight),
-          4 => return Ok(Square::MiddleLeft),
-          5 => return Ok(Square::MiddleMiddle),
-          6 => return Ok(Square::MiddleRight),
-          7 => return Ok(Square::TopLeft),
-          8 => return Ok(Square::TopMiddle),
-          9 => return Ok(Square::TopRight),
-          _ => eprintln!("number entered is not within the acceptable range"),
-        },
-        Err(error) => eprintln!("{}", error),
+      let wait = deadline.map_or(Self::POLL_TIME, |deadline| {
+        deadline
+          .saturating_duration_since(Instant::now())
+          .min(Self::POLL_TIME)
+      });
+
+      match lines.recv_timeout(wait) {
+        Ok(line) => return Ok(line?),
+        Err(RecvTimeoutError::Disconnected) => return Err(UserError::InputClosed),
+        Err(RecvTimeoutError::Timeout) => {
+          if clock.is_some_and(Clock::is_stopped) {
+            return Err(UserError::Stopped);
+          }
+          if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(UserError::OutOfTime);
+          }
+        }
       }
+    }
+  }
+}
+
+impl Default for User {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Player for User {
+  fn get_name(&self) -> &str {
+    "user"
+  }
 
-      eprintln!("please try again with a number between 1 and 9");
+  /// Fails when the user takes back a move, places it again or resigns, which only the
+  /// controller of a match can handle.
+  fn get_move(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    match self.get_action(game, clock)? {
+      Action::Mark(square) => Ok(square),
+      Action::Resign => Err(UserError::ResignationRefused.into()),
+      Action::Undo | Action::Redo => Err(UserError::TakeBackRefused.into()),
+    }
+  }
+
+  /// Asks until the square entered can be marked, explaining why it cannot otherwise: it may be
+  /// taken or, under the renju rule, forbidden. With a `clock`, gives up waiting once the time
+  /// for the move has run out or the clock is stopped.
+  fn get_action(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Action, PlayerError> {
+    let deadline = clock.and_then(|clock| {
+      let deadline = clock.get_deadline();
+      let instant = Instant::now().checked_add(deadline)?;
+      println!("{:.1} s left for this move", deadline.as_secs_f64());
+      Some(instant)
+    });
+
+    loop {
+      let square = match self.get_input(game, clock, deadline)? {
+        Action::Mark(square) => square,
+        action => return Ok(action),
+      };
+
+      match game.clone().place_mark(&square) {
+        Ok(()) => return Ok(Action::Mark(square)),
+        Err(error) => println!("{}", error),
+      }
     }
   }
 }
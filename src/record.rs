@@ -0,0 +1,510 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+  analysis::{self, AnalysisError},
+  controller::Forfeit,
+  game::{Game, GameError, Outcome, Side, Square},
+  player::{Mcts, MctsConfig, Minimax, Player},
+};
+use std::{
+  fs::{self, OpenOptions},
+  io::Write,
+  path::Path,
+  time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RecordError {
+  #[error(transparent)]
+  Analysis(#[from] AnalysisError),
+  #[error(transparent)]
+  Game(#[from] GameError),
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+  #[error("line {0} of the record is not valid: {1}")]
+  InvalidLine(usize, String),
+  #[error("the record has no Position tag")]
+  MissingPosition,
+  #[error("the record ends in the middle of a game")]
+  UnfinishedGame,
+  #[error("the file holds {0} games, there is no game {1}")]
+  NoSuchGame(usize, usize),
+}
+
+/// Movetext lines are wrapped before they get longer than this.
+const LINE_WIDTH: usize = 80;
+const UNFINISHED: &str = "*";
+
+/// Returns the result token of a game, as in PGN.
+fn get_result(outcome: Option<&Outcome>) -> &'static str {
+  match outcome {
+    Some(Outcome::XWin) => "1-0",
+    Some(Outcome::OWin) => "0-1",
+    Some(Outcome::Draw) => "1/2-1/2",
+    None => UNFINISHED,
+  }
+}
+
+/// Escapes the quotes and backslashes of a tag value with a backslash, as in PGN.
+fn escape(value: &str) -> String {
+  value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Reverses [`escape`], returning `None` when the value holds an unescaped quote or ends in the
+/// middle of an escape.
+fn unescape(value: &str) -> Option<String> {
+  let mut unescaped = String::with_capacity(value.len());
+  let mut characters = value.chars();
+
+  while let Some(character) = characters.next() {
+    match character {
+      '\\' => unescaped.push(characters.next()?),
+      '"' => return None,
+      _ => unescaped.push(character),
+    }
+  }
+
+  Some(unescaped)
+}
+
+/// Returns today's date in UTC as `YYYY.MM.DD`.
+fn get_date() -> String {
+  let days = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |duration| duration.as_secs() / 86400) as i64;
+  // Converts days since 1970-01-01 to a civil date, as in Howard Hinnant's `civil_from_days`.
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let day_of_era = z - era * 146097;
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 {
+    month_index + 3
+  } else {
+    month_index - 9
+  };
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+  format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// A game written like a PGN record: tag pairs such as `[X "minimax"]`, one per line, with any
+/// quote or backslash in the value escaped by a backslash, followed by the numbered moves and
+/// the result, `1-0` when x wins, `0-1` when o wins and `1/2-1/2` for a draw. The `Position` tag holds the position the game starts from, in the notation of
+/// `--position`, and tells the board and the rule. Moves are squares such as `b2`, or on the 3x3
+/// board also numbers picked as on the numeric keypad.
+pub(super) struct GameRecord {
+  tags: Vec<(String, String)>,
+  position: Game,
+  moves: Vec<Square>,
+}
+
+impl GameRecord {
+  /// Records `game`, played from `position` by `players`, x first. The side of `forfeit`, if
+  /// any, lost the game for the reason given there, which the `Termination` tag tells.
+  pub(super) fn new(
+    players: [String; 2],
+    seed: u64,
+    position: &Game,
+    game: &Game,
+    forfeit: Option<&(Side, Forfeit)>,
+  ) -> Self {
+    let [x, o] = players;
+    let result = match forfeit {
+      Some((Side::X, _)) => get_result(Some(&Outcome::OWin)),
+      Some((Side::O, _)) => get_result(Some(&Outcome::XWin)),
+      None => get_result(game.get_outcome().as_ref()),
+    };
+    let mut tags = vec![
+      ("X".to_string(), x),
+      ("O".to_string(), o),
+      ("Date".to_string(), get_date()),
+      ("Seed".to_string(), seed.to_string()),
+      ("Variant".to_string(), position.get_variant().to_string()),
+      ("Position".to_string(), position.to_string()),
+      ("Result".to_string(), result.to_string()),
+    ];
+
+    if let Some((_, forfeit)) = forfeit {
+      let termination = match forfeit {
+        Forfeit::Time => "time forfeit",
+        Forfeit::Resignation => "resignation",
+        Forfeit::IllegalMove => "illegal move",
+        Forfeit::EngineFailure => "engine failure",
+        Forfeit::Disconnection => "abandoned",
+      };
+      tags.push(("Termination".to_string(), termination.to_string()));
+    }
+
+    Self {
+      tags,
+      position: position.clone(),
+      moves: game.get_history()[position.get_history().len()..].to_vec(),
+    }
+  }
+
+  /// Appends the record to the file at `path`, creating it when necessary.
+  pub(super) fn append(&self, path: &Path) -> Result<(), std::io::Error> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", self)
+  }
+
+  /// Returns the game after every move has been played.
+  fn get_game(&self) -> Result<Game, GameError> {
+    let mut game = self.position.clone();
+
+    for square in &self.moves {
+      game.place_mark(square)?;
+    }
+
+    Ok(game)
+  }
+
+  fn get_tag(&self, name: &str) -> Option<&str> {
+    self
+      .tags
+      .iter()
+      .find(|(other, _)| other == name)
+      .map(|(_, value)| value.as_str())
+  }
+
+  /// Parses every game of `text`.
+  fn parse_all(text: &str) -> Result<Vec<Self>, RecordError> {
+    let mut records = Vec::new();
+    let mut tags = Vec::new();
+    let mut position: Option<Game> = None;
+    let mut game: Option<Game> = None;
+
+    for (index, line) in text.lines().enumerate() {
+      let line = line.trim();
+      let invalid = |reason: String| RecordError::InvalidLine(index + 1, reason);
+
+      if line.is_empty() {
+        continue;
+      }
+
+      if let Some(tag) = line.strip_prefix('[') {
+        let (name, value) = tag
+          .strip_suffix(']')
+          .and_then(|tag| tag.split_once(' '))
+          .and_then(|(name, value)| {
+            let value = value.strip_prefix('"')?.strip_suffix('"')?;
+            Some((name, unescape(value)?))
+          })
+          .ok_or_else(|| invalid("tags are written as [Name \"value\"]".to_string()))?;
+
+        if name == "Position" {
+          let start: Game = value
+            .parse()
+            .map_err(|error: GameError| invalid(error.to_string()))?;
+          game = Some(start.clone());
+          position = Some(start);
+        }
+
+        tags.push((name.to_string(), value));
+        continue;
+      }
+
+      let current = game.as_mut().ok_or(RecordError::MissingPosition)?;
+
+      for token in line.split_whitespace() {
+        if ["1-0", "0-1", "1/2-1/2", UNFINISHED].contains(&token) {
+          let start = position.take().ok_or(RecordError::MissingPosition)?;
+          let moves = current.get_history()[start.get_history().len()..].to_vec();
+          records.push(Self {
+            tags: std::mem::take(&mut tags),
+            position: start,
+            moves,
+          });
+          game = None;
+          break;
+        }
+
+        // Skips move numbers such as `1.` or `1...`, also when written before the move.
+        let square = match token.find('.') {
+          Some(dot)
+            if token[..dot]
+              .chars()
+              .all(|character| character.is_ascii_digit()) =>
+          {
+            token[dot..].trim_start_matches('.')
+          }
+          _ => token,
+        };
+
+        if square.is_empty() {
+          continue;
+        }
+
+        Self::parse_square(current, square)
+          .and_then(|square| current.place_mark(&square))
+          .map_err(|error| invalid(error.to_string()))?;
+      }
+    }
+
+    if !tags.is_empty() || game.is_some() {
+      return Err(RecordError::UnfinishedGame);
+    }
+
+    Ok(records)
+  }
+
+  /// Parses a square, or on the 3x3 board a number picked as on the numeric keypad.
+  fn parse_square(game: &Game, text: &str) -> Result<Square, GameError> {
+    let variant = game.get_variant();
+
+    match text.parse::<u8>() {
+      Ok(number @ 1..=9) if variant.get_width() == 3 && variant.get_height() == 3 => {
+        Ok(Square::new((number - 1) % 3, (number - 1) / 3))
+      }
+      _ => text.parse(),
+    }
+  }
+}
+
+impl std::fmt::Display for GameRecord {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    for (name, value) in &self.tags {
+      writeln!(f, "[{} \"{}\"]", name, escape(value))?;
+    }
+
+    writeln!(f)?;
+    let mut tokens = Vec::with_capacity(self.moves.len() * 2 + 2);
+    let mut side = self.position.get_side_to_move().clone();
+    let mut number = 1;
+
+    for (index, square) in self.moves.iter().enumerate() {
+      if side == Side::X {
+        tokens.push(format!("{}.", number));
+      } else if index == 0 {
+        tokens.push(format!("{}...", number));
+      }
+
+      tokens.push(square.to_string());
+
+      if side == Side::O {
+        number += 1;
+      }
+
+      side.switch();
+    }
+
+    let result = self.get_tag("Result").unwrap_or(UNFINISHED);
+    tokens.push(result.to_string());
+    let mut line = String::new();
+
+    for token in tokens {
+      if !line.is_empty() && line.len() + 1 + token.len() > LINE_WIDTH {
+        writeln!(f, "{}", line)?;
+        line.clear();
+      }
+
+      if !line.is_empty() {
+        line.push(' ');
+      }
+
+      line.push_str(&token);
+    }
+
+    writeln!(f, "{}", line)
+  }
+}
+
+/// Steps through game `game_number` of the records in the file at `path`, or through all of
+/// them, printing the board after every move together with the engine's evaluation of it.
+pub(super) fn replay(
+  path: &Path,
+  game_number: Option<usize>,
+  mcts_config: MctsConfig,
+  seed: u64,
+) -> Result<(), RecordError> {
+  let records = GameRecord::parse_all(&fs::read_to_string(path)?)?;
+  let records: Vec<&GameRecord> = match game_number {
+    Some(number @ 1..) if number <= records.len() => vec![&records[number - 1]],
+    Some(number) => return Err(RecordError::NoSuchGame(records.len(), number)),
+    None => records.iter().collect(),
+  };
+  let mut minimax = Minimax::new();
+  let mut mcts = Mcts::new(mcts_config);
+  mcts.new_game(seed);
+
+  for record in records {
+    println!();
+
+    for (name, value) in &record.tags {
+      println!("{}: {}", name, value);
+    }
+
+    let mut game = record.position.clone();
+    println!();
+    game.print_grid();
+    println!(
+      "evaluation: {}\n",
+      analysis::describe_position(&game, &mut minimax, &mut mcts)?
+    );
+
+    for (ply, square) in record.moves.iter().enumerate() {
+      let side = game.get_side_to_move().clone();
+      game.place_mark(square)?;
+      println!("ply {}: {} {}\n", ply + 1, side, square);
+      game.print_grid();
+      println!(
+        "evaluation: {}\n",
+        analysis::describe_position(&game, &mut minimax, &mut mcts)?
+      );
+    }
+
+    let recorded = record.get_tag("Result").unwrap_or(UNFINISHED);
+    let result = get_result(record.get_game()?.get_outcome().as_ref());
+
+    if recorded != result && record.get_tag("Termination").is_none() {
+      println!("the recorded result {} does not match the moves", recorded);
+    }
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{GameRecord, RecordError};
+  use crate::{
+    controller::Forfeit,
+    game::{Game, Side, Square, Variant},
+  };
+
+  fn play(position: &Game, squares: &[&str]) -> Game {
+    let mut game = position.clone();
+
+    for square in squares {
+      game.place_mark(&square.parse().unwrap()).unwrap();
+    }
+
+    game
+  }
+
+  fn record(position: &str, squares: &[&str], forfeit: Option<&(Side, Forfeit)>) -> GameRecord {
+    let position: Game = position.parse().unwrap();
+    let game = play(&position, squares);
+    GameRecord::new(
+      ["a".to_string(), "b".to_string()],
+      3,
+      &position,
+      &game,
+      forfeit,
+    )
+  }
+
+  #[test]
+  fn records_read_back_the_games_they_were_written_from() {
+    let games = [
+      record(
+        "___/___/___ x",
+        &["b2", "a1", "a2", "c2", "c1", "a3", "b3", "b1", "c3"],
+        None,
+      ),
+      record("x__/___/___ o", &["b2", "c3"], None),
+    ];
+    let text: String = games.iter().map(|record| format!("{}\n", record)).collect();
+    assert!(text.contains("1... b2 2. c3 *"));
+
+    let parsed = GameRecord::parse_all(&text).unwrap();
+    assert_eq!(parsed.len(), games.len());
+
+    for (parsed, written) in parsed.iter().zip(&games) {
+      assert_eq!(parsed.tags, written.tags);
+      assert_eq!(parsed.moves, written.moves);
+      assert!(parsed.position == written.position);
+      assert!(parsed.get_game().unwrap() == written.get_game().unwrap());
+    }
+
+    assert_eq!(parsed[0].get_tag("Result"), Some("1/2-1/2"));
+    assert_eq!(parsed[1].get_tag("Seed"), Some("3"));
+  }
+
+  #[test]
+  fn tag_values_with_quotes_and_backslashes_read_back() {
+    let name = r#"engine "v2" [beta] \ build\"#;
+    let position = Game::new(Variant::TIC_TAC_TOE);
+    let game = play(&position, &["b2"]);
+    let written = GameRecord::new(
+      [name.to_string(), "random".to_string()],
+      3,
+      &position,
+      &game,
+      None,
+    );
+    let text = written.to_string();
+    assert!(text.starts_with(r#"[X "engine \"v2\" [beta] \\ build\\"]"#));
+
+    let parsed = GameRecord::parse_all(&text).unwrap();
+    assert_eq!(parsed[0].get_tag("X"), Some(name));
+    assert_eq!(parsed[0].moves, written.moves);
+
+    assert!(matches!(
+      GameRecord::parse_all("[X \"a\"b\"]\n[Position \"___/___/___ x\"]\n*"),
+      Err(RecordError::InvalidLine(1, _))
+    ));
+  }
+
+  #[test]
+  fn moves_are_read_as_squares_or_keypad_numbers() {
+    let text = "[Position \"___/___/___ x\"]\n\n1. 5 7 2. c1 3...\n1-0\n";
+    let parsed = GameRecord::parse_all(text).unwrap();
+    assert_eq!(
+      parsed[0].moves,
+      [Square::new(1, 1), Square::new(0, 2), Square::new(2, 0)]
+    );
+
+    assert!(matches!(
+      GameRecord::parse_all("1. b2 *"),
+      Err(RecordError::MissingPosition)
+    ));
+    assert!(matches!(
+      GameRecord::parse_all("[Position \"___/___/___ x\"]\n1. b2"),
+      Err(RecordError::UnfinishedGame)
+    ));
+    assert!(matches!(
+      GameRecord::parse_all("[Position \"___/___/___ x\"]\n1. b2 b2 *"),
+      Err(RecordError::InvalidLine(2, _))
+    ));
+  }
+
+  #[test]
+  fn forfeits_set_the_result_and_termination() {
+    for (forfeit, termination) in [
+      (Forfeit::Time, "time forfeit"),
+      (Forfeit::Resignation, "resignation"),
+      (Forfeit::IllegalMove, "illegal move"),
+      (Forfeit::EngineFailure, "engine failure"),
+      (Forfeit::Disconnection, "abandoned"),
+    ] {
+      let written = record("___/___/___ x", &["b2"], Some(&(Side::O, forfeit)));
+      let parsed = GameRecord::parse_all(&written.to_string()).unwrap();
+      assert_eq!(parsed[0].get_tag("Result"), Some("1-0"));
+      assert_eq!(parsed[0].get_tag("Termination"), Some(termination));
+    }
+
+    let written = record("___/___/___ x", &[], Some(&(Side::X, Forfeit::Time)));
+    assert!(written.to_string().ends_with("\n0-1\n"));
+    assert_eq!(
+      record("___/___/___ x", &[], None).get_tag("Termination"),
+      None
+    );
+  }
+}
@@ -0,0 +1,327 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Solving small boards completely, into a table of the value and best moves of every
+//! position.
+
+use super::{
+  game::{Game, GameError, Outcome, Rule, Side, Square, Variant},
+  player::Evaluation,
+};
+use std::{
+  collections::HashMap,
+  fs::File,
+  io::{BufReader, BufWriter, Read, Write},
+  path::Path,
+};
+use thiserror::Error;
+
+/// Errors of solving, saving and loading tables.
+#[derive(Error, Debug)]
+pub enum SolverError {
+  /// A move could not be placed during the solving.
+  #[error(transparent)]
+  Game(#[from] GameError),
+  /// Saving or loading the table failed.
+  #[error(transparent)]
+  Io(#[from] std::io::Error),
+  /// The board has too many squares to solve.
+  #[error(
+    "boards of more than {} squares are too large to solve",
+    Table::SQUARES_MAXIMUM
+  )]
+  BoardTooLarge,
+  /// The file is not a table saved by the solver.
+  #[error("the file does not hold a solved table")]
+  InvalidTable,
+}
+
+const MAGIC: &[u8; 4] = b"TTTS";
+const WIN: u8 = 2;
+const DRAW: u8 = 1;
+const LOSS: u8 = 0;
+const PLIES_BITS: u32 = 6;
+
+/// Value of a position for the side to move, packed with the number of marks placed until the
+/// game ends into the top two bits and the other six bits of a byte.
+fn pack(evaluation: Evaluation) -> u8 {
+  match evaluation {
+    Evaluation::Win(plies) => WIN << PLIES_BITS | plies,
+    Evaluation::Draw => DRAW << PLIES_BITS,
+    Evaluation::Loss(plies) => LOSS << PLIES_BITS | plies,
+  }
+}
+
+fn unpack(value: u8) -> Option<Evaluation> {
+  let plies = value & ((1 << PLIES_BITS) - 1);
+
+  match value >> PLIES_BITS {
+    WIN => Some(Evaluation::Win(plies)),
+    DRAW => Some(Evaluation::Draw),
+    LOSS => Some(Evaluation::Loss(plies)),
+    _ => None,
+  }
+}
+
+/// Returns the value of the move for the side making it, from the value of the position it
+/// leads to.
+fn negate(evaluation: Evaluation) -> Evaluation {
+  match evaluation {
+    Evaluation::Win(plies) => Evaluation::Loss(plies + 1),
+    Evaluation::Draw => Evaluation::Draw,
+    Evaluation::Loss(plies) => Evaluation::Win(plies + 1),
+  }
+}
+
+/// Identifies a position by the contents of its squares, counted row by row from the bottom left
+/// as the digits of a base 3 number. The side to move follows from the number of marks.
+fn get_key(game: &Game) -> u32 {
+  let variant = game.get_variant();
+  let mut key = 0;
+
+  for row in (0..variant.get_height()).rev() {
+    for column in (0..variant.get_width()).rev() {
+      key = key * 3
+        + match game.get_mark(&Square::new(column, row)) {
+          None => 0,
+          Some(Side::X) => 1,
+          Some(Side::O) => 2,
+        };
+    }
+  }
+
+  key
+}
+
+/// Value and best moves of a position, as stored in a [`Table`].
+pub struct Solution {
+  evaluation: Evaluation,
+  best: u16,
+}
+
+impl Solution {
+  /// Returns the value of the position for the side to move.
+  pub fn get_evaluation(&self) -> Evaluation {
+    self.evaluation
+  }
+
+  /// Returns the squares that keep the value of the position, in the order of
+  /// [`Game::get_empty_squares`].
+  pub fn get_best_squares(&self, game: &Game) -> Vec<Square> {
+    game
+      .get_empty_squares()
+      .into_iter()
+      .filter(|square| self.best >> game.get_square_index(square) & 1 == 1)
+      .collect()
+  }
+}
+
+/// Value and best moves of every position reachable from the empty board, sorted by key. The
+/// best moves of a position are a bitmask of square indexes.
+#[derive(Clone)]
+pub struct Table {
+  variant: Variant,
+  keys: Vec<u32>,
+  values: Vec<u8>,
+  best: Vec<u16>,
+}
+
+impl Table {
+  /// The best moves of a position are kept in 16 bits, one per square.
+  pub const SQUARES_MAXIMUM: usize = u16::BITS as usize;
+
+  /// Enumerates every position reachable from the empty board of `variant` and solves it.
+  pub fn solve(variant: Variant) -> Result<Self, SolverError> {
+    if variant.get_square_count() > Self::SQUARES_MAXIMUM {
+      return Err(SolverError::BoardTooLarge);
+    }
+
+    let mut solutions = HashMap::new();
+    Self::solve_node(&mut Game::new(variant), &mut solutions)?;
+
+    let mut entries: Vec<(u32, (u8, u16))> = solutions.into_iter().collect();
+    entries.sort_unstable_by_key(|(key, _)| *key);
+
+    Ok(Self {
+      variant,
+      keys: entries.iter().map(|(key, _)| *key).collect(),
+      values: entries.iter().map(|(_, (value, _))| *value).collect(),
+      best: entries.iter().map(|(_, (_, best))| *best).collect(),
+    })
+  }
+
+  fn solve_node(
+    game: &mut Game,
+    solutions: &mut HashMap<u32, (u8, u16)>,
+  ) -> Result<Evaluation, SolverError> {
+    let key = get_key(game);
+
+    if let Some((value, _)) = solutions.get(&key) {
+      return unpack(*value).ok_or(SolverError::InvalidTable);
+    }
+
+    let (evaluation, best) = match game.get_outcome() {
+      Some(Outcome::Draw) => (Evaluation::Draw, 0),
+      Some(_) => (Evaluation::Loss(0), 0),
+      None => {
+        let mut moves = Vec::new();
+
+        for square in game.get_empty_squares() {
+          game.place_mark(&square)?;
+          let evaluation = negate(Self::solve_node(game, solutions)?);
+          game.undo_mark()?;
+          moves.push((game.get_square_index(&square), evaluation));
+        }
+
+        let evaluation = moves
+          .iter()
+          .map(|(_, evaluation)| *evaluation)
+          .max_by_key(Evaluation::get_rank)
+          .unwrap_or(Evaluation::Draw);
+        let best = moves
+          .iter()
+          .filter(|(_, other)| other.get_rank() == evaluation.get_rank())
+          .fold(0, |best, (index, _)| best | 1 << index);
+        (evaluation, best)
+      }
+    };
+
+    solutions.insert(key, (pack(evaluation), best));
+    Ok(evaluation)
+  }
+
+  /// Returns the board and the rule the table is solved for.
+  pub fn get_variant(&self) -> &Variant {
+    &self.variant
+  }
+
+  /// Returns the number of positions in the table.
+  pub fn get_positions_count(&self) -> usize {
+    self.keys.len()
+  }
+
+  /// Returns the solution of `game`, unless it is of another variant or not reachable.
+  pub fn get(&self, game: &Game) -> Option<Solution> {
+    if *game.get_variant() != self.variant {
+      return None;
+    }
+
+    let index = self.keys.binary_search(&get_key(game)).ok()?;
+
+    Some(Solution {
+      evaluation: unpack(self.values[index])?,
+      best: self.best[index],
+    })
+  }
+
+  /// Writes the table as a magic number, the variant, the number of positions and then every
+  /// key, value and best moves, little-endian.
+  pub fn save(&self, path: &Path) -> Result<(), SolverError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[
+      self.variant.get_width(),
+      self.variant.get_height(),
+      self.variant.get_k(),
+      match self.variant.get_rule() {
+        Rule::Freestyle => 0,
+        Rule::Exact => 1,
+        Rule::Renju => 2,
+      },
+    ])?;
+    writer.write_all(&(self.keys.len() as u32).to_le_bytes())?;
+
+    for index in 0..self.keys.len() {
+      writer.write_all(&self.keys[index].to_le_bytes())?;
+      writer.write_all(&[self.values[index]])?;
+      writer.write_all(&self.best[index].to_le_bytes())?;
+    }
+
+    writer.flush()?;
+    Ok(())
+  }
+
+  /// Reads a table written by [`Table::save`].
+  pub fn load(path: &Path) -> Result<Self, SolverError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut header = [0; 12];
+    reader.read_exact(&mut header)?;
+
+    if header[..4] != *MAGIC {
+      return Err(SolverError::InvalidTable);
+    }
+
+    let rule = match header[7] {
+      0 => Rule::Freestyle,
+      1 => Rule::Exact,
+      2 => Rule::Renju,
+      _ => return Err(SolverError::InvalidTable),
+    };
+    let variant = Variant::new(header[4], header[5], header[6], rule)?;
+    let count = u32::from_le_bytes([header[8], header[9], header[10], header[11]]) as usize;
+    let mut table = Self {
+      variant,
+      keys: Vec::with_capacity(count),
+      values: Vec::with_capacity(count),
+      best: Vec::with_capacity(count),
+    };
+
+    for _ in 0..count {
+      let mut entry = [0; 7];
+      reader.read_exact(&mut entry)?;
+      table
+        .keys
+        .push(u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]));
+      table.values.push(entry[4]);
+      table.best.push(u16::from_le_bytes([entry[5], entry[6]]));
+    }
+
+    if !table.keys.windows(2).all(|keys| keys[0] < keys[1]) {
+      return Err(SolverError::InvalidTable);
+    }
+
+    Ok(table)
+  }
+}
+
+/// Solves the empty board of `variant`, prints the number of positions, the value and the best
+/// moves, and writes the table to `output` when given.
+pub(crate) fn solve(variant: Variant, output: Option<&Path>) -> Result<(), SolverError> {
+  let table = Table::solve(variant)?;
+  let game = Game::new(variant);
+  println!("variant: {}", table.get_variant());
+  println!("positions: {}", table.get_positions_count());
+
+  if let Some(solution) = table.get(&game) {
+    let value = match solution.get_evaluation() {
+      Evaluation::Win(plies) => format!("x wins in {} marks", plies),
+      Evaluation::Draw => String::from("draw"),
+      Evaluation::Loss(plies) => format!("o wins in {} marks", plies),
+    };
+    let squares: Vec<String> = solution
+      .get_best_squares(&game)
+      .iter()
+      .map(Square::to_string)
+      .collect();
+    println!("value: {}", value);
+    println!("best moves: {}", squares.join(" "));
+  }
+
+  if let Some(output) = output {
+    table.save(output)?;
+    println!("table written to {}", output.display());
+  }
+
+  Ok(())
+}
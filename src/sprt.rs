@@ -0,0 +1,212 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+  controller::{Controller, ControllerError, GameCount, TimeControl},
+  game::Game,
+  player::{Context, PlayerError, PlayerSpec},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SprtError {
+  #[error(transparent)]
+  Controller(#[from] ControllerError),
+  #[error(transparent)]
+  Player(#[from] PlayerError),
+  #[error("users cannot take part in a test")]
+  UserInTest,
+  #[error("elo1 must be greater than elo0")]
+  InvalidBounds,
+  #[error("alpha and beta must be between 0 and 1")]
+  InvalidErrorRates,
+}
+
+/// Hypotheses and error rates of a sequential probability ratio test. H0 says that the player
+/// is `elo0` stronger than the baseline and H1 that it is `elo1` stronger.
+pub(super) struct Bounds {
+  elo0: f64,
+  elo1: f64,
+  alpha: f64,
+  beta: f64,
+}
+
+impl Bounds {
+  /// Pseudo-games of every result added to the ones played.
+  const PRIOR: f64 = 0.5;
+
+  pub(super) fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Result<Self, SprtError> {
+    if elo1 <= elo0 {
+      return Err(SprtError::InvalidBounds);
+    }
+
+    if alpha <= 0.0 || alpha >= 1.0 || beta <= 0.0 || beta >= 1.0 {
+      return Err(SprtError::InvalidErrorRates);
+    }
+
+    Ok(Self {
+      elo0,
+      elo1,
+      alpha,
+      beta,
+    })
+  }
+
+  /// Returns the log-likelihood ratios below which H0 and above which H1 is accepted.
+  fn get_limits(&self) -> (f64, f64) {
+    (
+      (self.beta / (1.0 - self.alpha)).ln(),
+      ((1.0 - self.beta) / self.alpha).ln(),
+    )
+  }
+
+  /// Returns the log-likelihood ratio of H1 against H0 after `wins`, `draws` and `losses`, using
+  /// the normal approximation of the score of a game with wins, draws and losses. Every result
+  /// is counted `PRIOR` more times than it occurred, so that the score has a variance even when
+  /// every game ends the same way.
+  fn get_llr(&self, wins: GameCount, draws: GameCount, losses: GameCount) -> f64 {
+    let [wins, draws, losses] = [wins, draws, losses].map(|count| count as f64 + Self::PRIOR);
+    let games = wins + draws + losses;
+    let (win, draw) = (wins / games, draws / games);
+    let score = win + draw / 2.0;
+    let variance = (win + draw / 4.0 - score * score) / games;
+    let (score0, score1) = (get_score(self.elo0), get_score(self.elo1));
+    (score1 - score0) * (2.0 * score - score0 - score1) / (2.0 * variance)
+  }
+}
+
+/// Returns the expected score of a player `elo` points stronger than its opponent.
+fn get_score(elo: f64) -> f64 {
+  1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Plays games between `player` and `baseline`, swapping sides after every game, until the test
+/// accepts one of the hypotheses or `maximum_games` have been played, printing the
+/// log-likelihood ratio after every game.
+pub(super) fn test(
+  [player, baseline]: [&PlayerSpec; 2],
+  bounds: &Bounds,
+  maximum_games: GameCount,
+  position: &Game,
+  time_control: Option<TimeControl>,
+  context: &Context,
+  seed: u64,
+) -> Result<(), SprtError> {
+  if player.is_interactive() || baseline.is_interactive() {
+    return Err(SprtError::UserInTest);
+  }
+
+  let (lower, upper) = bounds.get_limits();
+  println!("\nvariant: {}", position.get_variant());
+
+  if position.get_marks_count() > 0 {
+    println!("position: {}", position);
+  }
+
+  println!("player: {}\nbaseline: {}", player, baseline);
+  println!(
+    "H0: elo {}, H1: elo {}, alpha {}, beta {}, llr bounds ({:.2}, {:.2})\n",
+    bounds.elo0, bounds.elo1, bounds.alpha, bounds.beta, lower, upper
+  );
+  let mut controller = Controller::new(
+    player.get_player(context)?,
+    baseline.get_player(context)?,
+    maximum_games,
+    true,
+    position.clone(),
+    seed,
+  )
+  .with_time_control(time_control);
+
+  while controller.play_next_game()? {
+    let [results, baseline_results] = controller.get_results();
+    let (wins, draws, losses) = (
+      results.get_wins(),
+      results.get_draws(),
+      baseline_results.get_wins(),
+    );
+    let llr = bounds.get_llr(wins, draws, losses);
+    println!(
+      "games: {:>5}  +{} ={} -{}  llr: {:.3}",
+      wins + draws + losses,
+      wins,
+      draws,
+      losses,
+      llr
+    );
+
+    if llr <= lower {
+      println!(
+        "\nH0 accepted: the player is not {} elo stronger",
+        bounds.elo1
+      );
+      return Ok(());
+    }
+
+    if llr >= upper {
+      println!(
+        "\nH1 accepted: the player is {} elo stronger or more",
+        bounds.elo1
+      );
+      return Ok(());
+    }
+  }
+
+  println!("\nno hypothesis accepted after {} games", maximum_games);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Bounds, SprtError};
+
+  #[test]
+  fn bounds_need_ordered_hypotheses_and_error_rates_below_one() {
+    assert!(matches!(
+      Bounds::new(10.0, 10.0, 0.05, 0.05),
+      Err(SprtError::InvalidBounds)
+    ));
+    assert!(matches!(
+      Bounds::new(0.0, 10.0, 0.0, 0.05),
+      Err(SprtError::InvalidErrorRates)
+    ));
+    assert!(matches!(
+      Bounds::new(0.0, 10.0, 0.05, 1.0),
+      Err(SprtError::InvalidErrorRates)
+    ));
+
+    let (lower, upper) = Bounds::new(0.0, 10.0, 0.05, 0.05).unwrap().get_limits();
+    assert!((lower + 2.944439).abs() < 1e-6);
+    assert!((upper - 2.944439).abs() < 1e-6);
+  }
+
+  #[test]
+  fn log_likelihood_ratios_follow_the_score() {
+    let bounds = Bounds::new(0.0, 10.0, 0.05, 0.05).unwrap();
+    assert!((bounds.get_llr(60, 20, 20) - 1.725400).abs() < 1e-6);
+    assert!(bounds.get_llr(20, 20, 60) < -1.0);
+    assert!(bounds.get_llr(120, 40, 40) > bounds.get_llr(60, 20, 20));
+  }
+
+  #[test]
+  fn games_that_all_end_the_same_way_accept_a_hypothesis() {
+    let bounds = Bounds::new(0.0, 10.0, 0.05, 0.05).unwrap();
+    let (lower, upper) = bounds.get_limits();
+    assert!(bounds.get_llr(0, 30, 0) < 0.0);
+    assert!(bounds.get_llr(0, 100, 0) < lower);
+    assert!(bounds.get_llr(5, 0, 0) > 0.0);
+    assert!(bounds.get_llr(20, 0, 0) > upper);
+    assert!(bounds.get_llr(0, 0, 20) < lower);
+  }
+}
@@ -0,0 +1,300 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{
+  controller::{Controller, ControllerError, GameCount, TimeControl},
+  game::Game,
+  player::{self, Context, PlayerError, PlayerSpec},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TournamentError {
+  #[error(transparent)]
+  Controller(#[from] ControllerError),
+  #[error(transparent)]
+  Player(#[from] PlayerError),
+  #[error("users cannot take part in a tournament")]
+  UserInTournament,
+}
+
+/// Rounds of the minorization-maximization algorithm fitting the ratings.
+const RATING_ITERATIONS: usize = 10000;
+const RATING_TOLERANCE: f64 = 1e-9;
+/// Every pairing counts one extra draw, which keeps the ratings of players that won or lost
+/// every game finite.
+const PRIOR_DRAWS: f64 = 1.0;
+/// Multiplier of the standard error giving a 95% confidence interval.
+const CONFIDENCE_Z: f64 = 1.96;
+
+/// Points scored and games played by every player against every other one. A win scores one
+/// point and a draw half a point.
+struct Crosstable {
+  points: Vec<Vec<f64>>,
+  games: Vec<Vec<f64>>,
+}
+
+impl Crosstable {
+  fn new(players: usize) -> Self {
+    Self {
+      points: vec![vec![0.0; players]; players],
+      games: vec![vec![0.0; players]; players],
+    }
+  }
+
+  fn get_score(&self, player: usize) -> (f64, f64) {
+    (
+      self.points[player].iter().sum(),
+      self.games[player].iter().sum(),
+    )
+  }
+
+  /// Fits Bradley–Terry strengths to the results and returns them on the Elo scale, averaging 0,
+  /// each with the half-width of its 95% confidence interval. The intervals only account for
+  /// the games of the player itself, treating the ratings of its opponents as exact.
+  fn get_ratings(&self) -> Vec<(f64, f64)> {
+    let players = self.points.len();
+    let pairs = |i: usize, j: usize| i != j && self.games[i][j] > 0.0;
+    let points = |i: usize, j: usize| self.points[i][j] + PRIOR_DRAWS / 2.0;
+    let games = |i: usize, j: usize| self.games[i][j] + PRIOR_DRAWS;
+    let mut strengths = vec![1.0; players];
+
+    for _ in 0..RATING_ITERATIONS {
+      let mut change: f64 = 0.0;
+
+      for i in 0..players {
+        let (won, expected) =
+          (0..players)
+            .filter(|j| pairs(i, *j))
+            .fold((0.0, 0.0), |(won, expected), j| {
+              (
+                won + points(i, j),
+                expected + games(i, j) / (strengths[i] + strengths[j]),
+              )
+            });
+
+        if expected > 0.0 {
+          let strength = won / expected;
+          change = change.max((strength / strengths[i]).ln().abs());
+          strengths[i] = strength;
+        }
+      }
+
+      let mean = strengths
+        .iter()
+        .map(|strength| f64::ln(*strength))
+        .sum::<f64>()
+        / players as f64;
+
+      for strength in strengths.iter_mut() {
+        *strength /= mean.exp();
+      }
+
+      if change < RATING_TOLERANCE {
+        break;
+      }
+    }
+
+    let elo = 400.0 / std::f64::consts::LN_10;
+
+    (0..players)
+      .map(|i| {
+        let information: f64 = (0..players)
+          .filter(|j| pairs(i, *j))
+          .map(|j| {
+            let expected = strengths[i] / (strengths[i] + strengths[j]);
+            games(i, j) * expected * (1.0 - expected)
+          })
+          .sum();
+        let error = if information > 0.0 {
+          CONFIDENCE_Z * elo / information.sqrt()
+        } else {
+          f64::INFINITY
+        };
+        (elo * strengths[i].ln(), error)
+      })
+      .collect()
+  }
+}
+
+/// Plays `games` games between every pair of `specs`, with the players swapping sides after
+/// every game, then prints a crosstable and the ratings of the players.
+pub(super) fn play(
+  specs: &[PlayerSpec],
+  games: GameCount,
+  position: &Game,
+  time_control: Option<TimeControl>,
+  context: &Context,
+  seed: u64,
+) -> Result<(), TournamentError> {
+  if specs.iter().any(PlayerSpec::is_interactive) {
+    return Err(TournamentError::UserInTournament);
+  }
+
+  println!("\nvariant: {}", position.get_variant());
+
+  if position.get_marks_count() > 0 {
+    println!("position: {}", position);
+  }
+
+  println!("games per pairing: {}\n", games);
+  let mut crosstable = Crosstable::new(specs.len());
+  let mut pairing = 0;
+
+  for i in 0..specs.len() {
+    for j in i + 1..specs.len() {
+      let results = Controller::new(
+        specs[i].get_player(context)?,
+        specs[j].get_player(context)?,
+        games,
+        true,
+        position.clone(),
+        player::derive_seed(seed, pairing),
+      )
+      .with_time_control(time_control)
+      .play_match()?;
+      pairing += 1;
+      // The match ends early when a player leaves, so only the games played are counted.
+      let played = results[0].get_wins() + results[0].get_draws() + results[1].get_wins();
+
+      for (player, opponent, results) in [(i, j, &results[0]), (j, i, &results[1])] {
+        crosstable.points[player][opponent] +=
+          results.get_wins() as f64 + results.get_draws() as f64 / 2.0;
+        crosstable.games[player][opponent] += played as f64;
+      }
+
+      println!(
+        "{} vs {}: +{} ={} -{}",
+        specs[i],
+        specs[j],
+        results[0].get_wins(),
+        results[0].get_draws(),
+        results[1].get_wins()
+      );
+    }
+  }
+
+  print_crosstable(specs, &crosstable);
+  print_ratings(specs, &crosstable);
+  Ok(())
+}
+
+fn print_crosstable(specs: &[PlayerSpec], crosstable: &Crosstable) {
+  let width = specs
+    .iter()
+    .map(|spec| spec.to_string().len())
+    .fold("player".len(), usize::max);
+  print!("\n{:>3}  {:width$}", "#", "player");
+
+  for index in 0..specs.len() {
+    print!("  {:>5}", index + 1);
+  }
+
+  println!("  score");
+
+  for (i, spec) in specs.iter().enumerate() {
+    print!("{:>3}  {:width$}", i + 1, spec.to_string());
+
+    for j in 0..specs.len() {
+      if i == j {
+        print!("  {:>5}", "-");
+      } else {
+        print!("  {:>5.1}", crosstable.points[i][j]);
+      }
+    }
+
+    let (points, games) = crosstable.get_score(i);
+    println!("  {:.1}/{}", points, games);
+  }
+}
+
+fn print_ratings(specs: &[PlayerSpec], crosstable: &Crosstable) {
+  let width = specs
+    .iter()
+    .map(|spec| spec.to_string().len())
+    .fold("player".len(), usize::max);
+  let mut ratings: Vec<(usize, (f64, f64))> =
+    crosstable.get_ratings().into_iter().enumerate().collect();
+  ratings.sort_by(|(_, (a, _)), (_, (b, _))| b.total_cmp(a));
+  println!(
+    "\n{:>4}  {:width$}  {:>6}  {:>6}",
+    "rank", "player", "elo", "±95%"
+  );
+
+  for (rank, (index, (elo, error))) in ratings.into_iter().enumerate() {
+    println!(
+      "{:>4}  {:width$}  {:>6.0}  {:>6.0}",
+      rank + 1,
+      specs[index].to_string(),
+      elo,
+      error
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Crosstable, PRIOR_DRAWS};
+
+  /// Makes a crosstable where player `i` scored `points[i][j]` in `games` games against `j`.
+  fn get_crosstable(points: &[&[f64]], games: f64) -> Crosstable {
+    let mut crosstable = Crosstable::new(points.len());
+
+    for (i, row) in points.iter().enumerate() {
+      for (j, points) in row.iter().enumerate() {
+        if i != j {
+          crosstable.points[i][j] = *points;
+          crosstable.games[i][j] = games;
+        }
+      }
+    }
+
+    crosstable
+  }
+
+  #[test]
+  fn even_results_give_even_ratings() {
+    let ratings = get_crosstable(&[&[0.0, 5.0], &[5.0, 0.0]], 10.0).get_ratings();
+    assert!(ratings[0].0.abs() < 1e-6 && ratings[1].0.abs() < 1e-6);
+    assert!((ratings[0].1 - ratings[1].1).abs() < 1e-9);
+  }
+
+  #[test]
+  fn two_players_differ_by_the_elo_of_their_score() {
+    let ratings = get_crosstable(&[&[0.0, 75.0], &[25.0, 0.0]], 100.0).get_ratings();
+    let score = (75.0 + PRIOR_DRAWS / 2.0) / (100.0 + PRIOR_DRAWS);
+    let difference = 400.0 * (score / (1.0 - score)).log10();
+    assert!((ratings[0].0 - ratings[1].0 - difference).abs() < 1e-6);
+    assert!((ratings[0].0 + ratings[1].0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn ratings_follow_the_results_and_narrow_with_more_games() {
+    let points: &[&[f64]] = &[&[0.0, 7.0, 9.0], &[3.0, 0.0, 6.0], &[1.0, 4.0, 0.0]];
+    let ratings = get_crosstable(points, 10.0).get_ratings();
+    assert!(ratings[0].0 > ratings[1].0 && ratings[1].0 > ratings[2].0);
+    assert!(ratings.iter().map(|(rating, _)| rating).sum::<f64>().abs() < 1e-6);
+
+    let scaled: Vec<Vec<f64>> = points
+      .iter()
+      .map(|row| row.iter().map(|points| points * 10.0).collect())
+      .collect();
+    let scaled: Vec<&[f64]> = scaled.iter().map(Vec::as_slice).collect();
+    let more = get_crosstable(&scaled, 100.0).get_ratings();
+
+    for (few, many) in ratings.iter().zip(&more) {
+      assert!(many.1 < few.1 && many.1.is_finite());
+    }
+  }
+}
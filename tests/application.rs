@@ -0,0 +1,68 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::process::{Command, Output};
+
+fn run(arguments: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_tic-tac-toe"))
+    .args(arguments)
+    .output()
+    .unwrap()
+}
+
+#[test]
+fn commands_reject_the_arguments_they_do_not_use() {
+  for arguments in [
+    &["analyze", "--gomoku", "xo_/_o_/__x x"][..],
+    &["analyze", "--table", "missing.table", "xo_/_o_/__x x"],
+    &["replay", "missing.record", "--time-ms", "100"],
+    &["engine", "random", "--width", "4"],
+    &["--width", "4", "tournament", "random", "random"],
+    &["-x", "random", "solve"],
+    &["analyze", "--mcts-c=nan", "xo_/_o_/__x x"],
+    &["analyze", "--mcts-c=-3", "xo_/_o_/__x x"],
+    &["analyze", "--mcts-rave=-5", "xo_/_o_/__x x"],
+    &[
+      "analyze",
+      "--mcts-capacity=18446744073709551615",
+      "xo_/_o_/__x x",
+    ],
+  ] {
+    let output = run(arguments);
+    assert_eq!(output.status.code(), Some(2), "{:?}", arguments);
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error:"));
+  }
+}
+
+#[test]
+fn commands_take_the_board_size_they_play_on() {
+  let output = run(&["solve", "--width", "2", "--height", "2", "-k", "2"]);
+  assert!(output.status.success());
+  assert!(String::from_utf8_lossy(&output.stdout).contains("x wins in 3 marks"));
+
+  let output = run(&[
+    "--seed",
+    "1",
+    "tournament",
+    "random",
+    "random",
+    "--games",
+    "2",
+    "--width",
+    "4",
+    "-k",
+    "4",
+  ]);
+  assert!(output.status.success());
+}
@@ -0,0 +1,438 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, thread, time::Duration};
+use tic_tac_toe::{
+  controller::{Controller, ControllerError, Ending, GameCount, Listener, Results, TimeControl},
+  game::{Game, GameError, Square, Variant},
+  player::{Action, Clock, ConnectionError, Minimax, Player, PlayerError, Random},
+};
+
+/// Writes down every event of a match, one line each.
+struct Log {
+  lines: Rc<RefCell<Vec<String>>>,
+}
+
+impl Listener for Log {
+  fn notify_match_start(
+    &mut self,
+    players: &[Box<dyn Player>; 2],
+    _position: &Game,
+    _alternate: bool,
+  ) -> Result<(), ControllerError> {
+    let line = format!("match {} {}", players[0], players[1]);
+    self.lines.borrow_mut().push(line);
+    Ok(())
+  }
+
+  fn notify_game_start(
+    &mut self,
+    number: GameCount,
+    _seed: u64,
+    x: usize,
+    _game: &Game,
+  ) -> Result<(), ControllerError> {
+    self
+      .lines
+      .borrow_mut()
+      .push(format!("game {} x {}", number, x));
+    Ok(())
+  }
+
+  fn notify_move(
+    &mut self,
+    _game: &Game,
+    square: &Square,
+    _elapsed: Duration,
+  ) -> Result<(), ControllerError> {
+    self.lines.borrow_mut().push(format!("move {}", square));
+    Ok(())
+  }
+
+  fn notify_undo(&mut self, game: &Game) -> Result<(), ControllerError> {
+    let line = format!("undo to {} marks", game.get_history().len());
+    self.lines.borrow_mut().push(line);
+    Ok(())
+  }
+
+  fn notify_redo(&mut self, game: &Game) -> Result<(), ControllerError> {
+    let line = format!("redo to {} marks", game.get_history().len());
+    self.lines.borrow_mut().push(line);
+    Ok(())
+  }
+
+  fn notify_undo_refused(&mut self, _game: &Game) -> Result<(), ControllerError> {
+    self.lines.borrow_mut().push("undo refused".to_string());
+    Ok(())
+  }
+
+  fn notify_redo_refused(&mut self, _game: &Game) -> Result<(), ControllerError> {
+    self.lines.borrow_mut().push("redo refused".to_string());
+    Ok(())
+  }
+
+  fn notify_game_end(
+    &mut self,
+    _players: &[Box<dyn Player>; 2],
+    _position: &Game,
+    game: &Game,
+    ending: &Ending,
+  ) -> Result<(), ControllerError> {
+    assert_eq!(ending.get_moves().len(), game.get_history().len());
+    let line = match ending.get_forfeit() {
+      Some((side, forfeit)) => format!("end {} ({} by {})", ending.get_outcome(), forfeit, side),
+      None => format!("end {}", ending.get_outcome()),
+    };
+    self.lines.borrow_mut().push(line);
+    Ok(())
+  }
+
+  fn notify_match_end(
+    &mut self,
+    _players: &[Box<dyn Player>; 2],
+    results: &[Results; 2],
+    games: GameCount,
+  ) -> Result<(), ControllerError> {
+    let line = format!(
+      "results {} {} {} of {}",
+      results[0].get_wins(),
+      results[0].get_draws(),
+      results[1].get_wins(),
+      games
+    );
+    self.lines.borrow_mut().push(line);
+    Ok(())
+  }
+}
+
+/// Marks the first empty square after waiting for `delay`.
+struct Slow {
+  delay: Duration,
+}
+
+impl Player for Slow {
+  fn get_name(&self) -> &str {
+    "slow"
+  }
+
+  fn get_move(&mut self, game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    thread::sleep(self.delay);
+    Ok(game.get_empty_squares()[0])
+  }
+}
+
+/// Marks the first empty square after waiting for `delay`, writing down the deadline of every
+/// move.
+struct Watched {
+  delay: Duration,
+  deadlines: Rc<RefCell<Vec<Duration>>>,
+}
+
+impl Player for Watched {
+  fn get_name(&self) -> &str {
+    "watched"
+  }
+
+  fn get_move(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    self
+      .deadlines
+      .borrow_mut()
+      .push(clock.unwrap().get_deadline());
+    thread::sleep(self.delay);
+    Ok(game.get_empty_squares()[0])
+  }
+}
+
+/// Takes the given actions in turn, then marks the first empty square. With `external`, it
+/// plays like another program.
+struct Scripted {
+  actions: VecDeque<Action>,
+  external: bool,
+}
+
+impl Player for Scripted {
+  fn get_name(&self) -> &str {
+    "scripted"
+  }
+
+  fn get_move(&mut self, game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    Ok(game.get_empty_squares()[0])
+  }
+
+  fn get_action(&mut self, game: &Game, clock: Option<&Clock>) -> Result<Action, PlayerError> {
+    match self.actions.pop_front() {
+      Some(action) => Ok(action),
+      None => Ok(Action::Mark(self.get_move(game, clock)?)),
+    }
+  }
+
+  fn is_external(&self) -> bool {
+    self.external
+  }
+}
+
+fn play_logged(controller: Controller) -> Vec<String> {
+  let lines = Rc::new(RefCell::new(Vec::new()));
+  controller
+    .with_listener(Box::new(Log {
+      lines: lines.clone(),
+    }))
+    .play_match()
+    .unwrap();
+  lines.take()
+}
+
+fn random_match(seed: u64) -> Controller {
+  Controller::new(
+    Box::new(Random::new()),
+    Box::new(Random::new()),
+    3,
+    true,
+    Game::new(Variant::TIC_TAC_TOE),
+    seed,
+  )
+}
+
+#[test]
+fn listeners_follow_every_game() {
+  let lines = play_logged(random_match(7));
+  let count = |prefix: &str| lines.iter().filter(|line| line.starts_with(prefix)).count();
+  assert_eq!(lines[0], "match random random");
+  assert_eq!(lines[1], "game 1 x 0");
+  assert_eq!(count("game "), 3);
+  assert_eq!(count("end "), 3);
+  assert!(lines.contains(&"game 2 x 1".to_string()));
+  assert!(lines.last().unwrap().starts_with("results "));
+  assert!(lines.last().unwrap().ends_with(" of 3"));
+  assert!(count("move ") >= 3 * 5);
+}
+
+#[test]
+fn equal_seeds_replay_equal_matches() {
+  assert_eq!(play_logged(random_match(8)), play_logged(random_match(8)));
+}
+
+#[test]
+fn players_over_the_move_limit_lose_on_time() {
+  let controller = Controller::new(
+    Box::new(Slow {
+      delay: Duration::from_millis(50),
+    }),
+    Box::new(Random::new()),
+    1,
+    false,
+    Game::new(Variant::TIC_TAC_TOE),
+    9,
+  )
+  .with_time_control(TimeControl::new(
+    None,
+    Duration::ZERO,
+    Some(Duration::from_millis(10)),
+  ));
+  let lines = play_logged(controller);
+  assert_eq!(lines[2], "end o win (time by x)");
+  assert_eq!(lines[3], "results 0 0 1 of 1");
+}
+
+/// Fails every move the way an engine reporting an error does.
+struct Failing;
+
+impl Player for Failing {
+  fn get_name(&self) -> &str {
+    "failing"
+  }
+
+  fn get_move(&mut self, _game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    Err(ConnectionError::Failed("out of memory".to_string()).into())
+  }
+}
+
+#[test]
+fn players_making_illegal_moves_or_failing_forfeit() {
+  let illegal = |external| {
+    Controller::new(
+      Box::new(Scripted {
+        actions: VecDeque::from([
+          Action::Mark(Square::new(1, 1)),
+          Action::Mark(Square::new(1, 1)),
+        ]),
+        external,
+      }),
+      Box::new(Slow {
+        delay: Duration::ZERO,
+      }),
+      1,
+      false,
+      Game::new(Variant::TIC_TAC_TOE),
+      11,
+    )
+  };
+  assert!(matches!(
+    illegal(false).play_match(),
+    Err(ControllerError::Game(GameError::SquareIsNotEmpty(_)))
+  ));
+
+  let lines = play_logged(illegal(true));
+  assert_eq!(lines[4], "end o win (illegal move by x)");
+  assert_eq!(lines[5], "results 0 0 1 of 1");
+
+  let controller = Controller::new(
+    Box::new(Random::new()),
+    Box::new(Failing),
+    2,
+    true,
+    Game::new(Variant::TIC_TAC_TOE),
+    12,
+  );
+  let lines = play_logged(controller);
+  assert!(lines.contains(&"end x win (engine failure by o)".to_string()));
+  assert!(lines.contains(&"end o win (engine failure by x)".to_string()));
+  assert_eq!(lines.last().unwrap(), "results 2 0 0 of 2");
+}
+
+#[test]
+fn users_take_back_moves_and_place_them_again() {
+  let controller = Controller::new(
+    Box::new(Scripted {
+      actions: VecDeque::from([
+        Action::Undo,
+        Action::Redo,
+        Action::Mark(Square::new(1, 1)),
+        Action::Undo,
+        Action::Mark(Square::new(1, 1)),
+        Action::Undo,
+        Action::Redo,
+        Action::Redo,
+      ]),
+      external: false,
+    }),
+    Box::new(Slow {
+      delay: Duration::ZERO,
+    }),
+    1,
+    false,
+    Game::new(Variant::TIC_TAC_TOE),
+    10,
+  );
+  let lines = play_logged(controller);
+  assert_eq!(
+    lines[2..11],
+    [
+      "undo refused",
+      "redo refused",
+      "move b2",
+      "move a3",
+      "undo to 0 marks",
+      "move b2",
+      "move a3",
+      "undo to 0 marks",
+      "redo to 2 marks",
+    ]
+  );
+  assert_eq!(lines[11], "redo refused");
+}
+
+#[test]
+fn alternating_players_swap_sides_every_game() {
+  let play = |alternate| {
+    Controller::new(
+      Box::new(Minimax::new()),
+      Box::new(Slow {
+        delay: Duration::ZERO,
+      }),
+      4,
+      alternate,
+      Game::new(Variant::TIC_TAC_TOE),
+      13,
+    )
+  };
+
+  let results = play(false).play_match().unwrap();
+  assert_eq!(
+    (results[0].get_wins_as_x(), results[0].get_wins_as_o()),
+    (4, 0)
+  );
+
+  let lines = play_logged(play(true));
+  let starts: Vec<&String> = lines
+    .iter()
+    .filter(|line| line.starts_with("game "))
+    .collect();
+  assert_eq!(
+    starts,
+    ["game 1 x 0", "game 2 x 1", "game 3 x 0", "game 4 x 1"]
+  );
+  assert_eq!(lines.last().unwrap(), "results 4 0 0 of 4");
+
+  let results = play(true).play_match().unwrap();
+  assert_eq!(
+    (results[0].get_wins_as_x(), results[0].get_wins_as_o()),
+    (2, 2)
+  );
+  assert_eq!(results[1].get_wins() + results[1].get_draws(), 0);
+}
+
+#[test]
+fn clocks_run_out_over_the_game_unless_the_increment_refills_them() {
+  let play = |increment| {
+    let deadlines = Rc::new(RefCell::new(Vec::new()));
+    let controller = Controller::new(
+      Box::new(Watched {
+        delay: Duration::from_millis(40),
+        deadlines: deadlines.clone(),
+      }),
+      Box::new(Random::new()),
+      1,
+      false,
+      Game::new(Variant::TIC_TAC_TOE),
+      14,
+    )
+    .with_time_control(TimeControl::new(
+      Some(Duration::from_millis(100)),
+      increment,
+      None,
+    ));
+    let lines = play_logged(controller);
+    (lines, deadlines.take())
+  };
+
+  let (lines, deadlines) = play(Duration::ZERO);
+  assert_eq!(lines[lines.len() - 2], "end o win (time by x)");
+  assert_eq!(deadlines.len(), 3);
+  assert_eq!(deadlines[0], Duration::from_millis(100));
+  assert!(deadlines[1] <= Duration::from_millis(60));
+  assert!(deadlines[2] <= Duration::from_millis(20));
+
+  let (lines, deadlines) = play(Duration::from_millis(50));
+  assert!(!lines[lines.len() - 2].contains("time"));
+  assert!(deadlines.len() >= 3);
+  assert!(deadlines[1] > deadlines[0]);
+}
+
+#[test]
+fn clock_budgets_share_the_remaining_time_within_the_deadline() {
+  let second = Duration::from_secs(1);
+  let clock = Clock::new(10 * second, second, None);
+  assert_eq!(clock.get_deadline(), 10 * second);
+  assert_eq!(clock.get_budget(10), second.mul_f64(1.6));
+  assert_eq!(clock.get_budget(0), second.mul_f64(8.0));
+
+  let clock = Clock::new(10 * second, second, Some(second / 2));
+  assert_eq!(clock.get_deadline(), second / 2);
+  assert_eq!(clock.get_budget(10), second.mul_f64(0.4));
+  assert_eq!(
+    Clock::new(Duration::ZERO, second, None).get_budget(5),
+    Duration::ZERO
+  );
+}
@@ -0,0 +1,140 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Cursor;
+use tic_tac_toe::{
+  controller::Controller,
+  engine,
+  game::{Game, Square, Variant},
+  player::{
+    Action, Clock, Context, External, ExternalError, MctsConfig, Minimax, Player, PlayerError,
+    PlayerSpec, Random,
+  },
+};
+
+/// Takes back a move on every turn, as a person playing over the protocol may try to.
+struct TakingBack;
+
+impl Player for TakingBack {
+  fn get_name(&self) -> &str {
+    "taking back"
+  }
+
+  fn get_move(&mut self, game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    Ok(game.get_empty_squares()[0])
+  }
+
+  fn get_action(&mut self, _game: &Game, _clock: Option<&Clock>) -> Result<Action, PlayerError> {
+    Ok(Action::Undo)
+  }
+}
+
+/// Starts this crate's binary as an engine serving `player`.
+fn start_engine(player: &str) -> External {
+  External::new(&format!(
+    "{} engine {}",
+    env!("CARGO_BIN_EXE_tic-tac-toe"),
+    player
+  ))
+  .unwrap()
+}
+
+#[test]
+fn engines_reply_to_every_go() {
+  let input = "tictactoe\n\
+    newgame 1\n\
+    position x__/_o_/___ x moves a2\n\
+    go movetime 100\n\
+    position x__/_o_/___ x moves c3\n\
+    dance\n\
+    go\n\
+    position x__/___ x\n\
+    go\n\
+    quit\n\
+    go\n";
+  let mut output = Vec::new();
+  engine::serve(Box::new(Minimax::new()), Cursor::new(input), &mut output).unwrap();
+
+  let output = String::from_utf8(output).unwrap();
+  let lines: Vec<&str> = output.lines().collect();
+  assert_eq!(
+    lines[..5],
+    [
+      "id name minimax",
+      "ok",
+      "bestmove a1",
+      "info 'dance' is not a command",
+      "bestmove b3",
+    ]
+  );
+  assert!(lines[5].starts_with("error "));
+  assert_eq!(lines.len(), 6);
+}
+
+#[test]
+fn engines_reply_an_error_to_taking_back_moves() {
+  let input = "tictactoe\nposition x__/_o_/___ x\ngo\nquit\n";
+  let mut output = Vec::new();
+  engine::serve(Box::new(TakingBack), Cursor::new(input), &mut output).unwrap();
+
+  let output = String::from_utf8(output).unwrap();
+  assert_eq!(
+    output.lines().collect::<Vec<_>>(),
+    [
+      "id name taking back",
+      "ok",
+      "error moves cannot be taken back here"
+    ]
+  );
+}
+
+#[test]
+fn external_engines_never_lose_to_random() {
+  let engine = start_engine("minimax");
+  assert_eq!(engine.get_name(), "minimax");
+
+  let results = Controller::new(
+    Box::new(engine),
+    Box::new(Random::new()),
+    6,
+    true,
+    Game::new(Variant::TIC_TAC_TOE),
+    1,
+  )
+  .play_match()
+  .unwrap();
+  assert_eq!(results[1].get_wins(), 0);
+}
+
+#[test]
+fn external_engines_need_a_command() {
+  let context = Context::new(
+    MctsConfig::new(
+      MctsConfig::ROUNDS_DEFAULT,
+      MctsConfig::EXPLORATION_DEFAULT,
+      None,
+      MctsConfig::NODES_CAPACITY_DEFAULT,
+    ),
+    None,
+  );
+  let spec: PlayerSpec = "external".parse().unwrap();
+  assert!(matches!(
+    spec.get_player(&context),
+    Err(PlayerError::External(ExternalError::MissingCommand))
+  ));
+  assert!(matches!(
+    External::new("tic-tac-toe-does-not-exist"),
+    Err(PlayerError::External(ExternalError::Io(_)))
+  ));
+}
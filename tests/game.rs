@@ -0,0 +1,339 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use tic_tac_toe::game::{Game, GameError, Outcome, Rule, Side, Square, Variant};
+
+fn play(game: &mut Game, squares: &[&str]) {
+  for square in squares {
+    game.place_mark(&square.parse().unwrap()).unwrap();
+  }
+}
+
+#[test]
+fn squares_are_written_with_a_column_letter_and_a_row_number() {
+  let square: Square = "b3".parse().unwrap();
+  assert_eq!(square, Square::new(1, 2));
+  assert_eq!(square.to_string(), "b3");
+  assert!("3b".parse::<Square>().is_err());
+}
+
+#[test]
+fn a_row_of_three_wins() {
+  let mut game = Game::new(Variant::TIC_TAC_TOE);
+  play(&mut game, &["a1", "b1", "a2", "b2", "a3"]);
+  assert_eq!(*game.get_outcome(), Some(Outcome::XWin));
+  assert!(matches!(
+    game.place_mark(&Square::new(2, 2)),
+    Err(GameError::GameIsOver(Outcome::XWin))
+  ));
+}
+
+#[test]
+fn a_full_board_without_a_row_is_a_draw() {
+  let mut game = Game::new(Variant::TIC_TAC_TOE);
+  play(
+    &mut game,
+    &["b2", "a1", "c1", "a3", "a2", "c2", "b1", "b3", "c3"],
+  );
+  assert_eq!(*game.get_outcome(), Some(Outcome::Draw));
+  assert!(game.get_empty_squares().is_empty());
+}
+
+#[test]
+fn marks_cannot_be_placed_twice_or_off_the_board() {
+  let mut game = Game::new(Variant::TIC_TAC_TOE);
+  play(&mut game, &["b2"]);
+  assert!(matches!(
+    game.place_mark(&Square::new(1, 1)),
+    Err(GameError::SquareIsNotEmpty(_))
+  ));
+  assert!(matches!(
+    game.place_mark(&Square::new(3, 0)),
+    Err(GameError::SquareIsOffTheBoard(_))
+  ));
+  assert_eq!(*game.get_side_to_move(), Side::O);
+}
+
+#[test]
+fn marks_taken_back_are_placed_again_until_another_mark_is_placed() {
+  let mut game = Game::new(Variant::TIC_TAC_TOE);
+  play(&mut game, &["a1", "b1", "a2", "b2", "a3"]);
+  assert_eq!(game.undo_mark().unwrap(), Square::new(0, 2));
+  assert_eq!(*game.get_outcome(), None);
+  assert_eq!(game.redo_mark().unwrap(), Square::new(0, 2));
+  assert_eq!(*game.get_outcome(), Some(Outcome::XWin));
+
+  game.undo_mark().unwrap();
+  play(&mut game, &["c3"]);
+  assert!(game.get_undone().is_empty());
+  assert!(matches!(game.redo_mark(), Err(GameError::NothingToRedo)));
+}
+
+#[test]
+fn positions_are_read_back_from_their_notation() {
+  let mut game = Game::new(Variant::new(5, 4, 4, Rule::Exact).unwrap());
+  play(&mut game, &["c2", "d3", "b2"]);
+  let text = game.to_string();
+  assert_eq!(text, "_____/___o_/_xx__/_____ o 4 exact");
+
+  let parsed: Game = text.parse().unwrap();
+  assert!(parsed == game);
+  assert_eq!(parsed.get_variant(), game.get_variant());
+  assert_eq!(parsed.get_mark(&Square::new(3, 2)), Some(Side::O));
+}
+
+#[test]
+fn positions_that_cannot_occur_are_rejected() {
+  assert!(matches!(
+    "xx_/___/___ o".parse::<Game>(),
+    Err(GameError::IllegalPosition(_))
+  ));
+  assert!(matches!(
+    "xxx/oo_/o__ x".parse::<Game>(),
+    Err(GameError::IllegalPosition(_))
+  ));
+}
+
+#[test]
+fn variants_must_fit_their_rows() {
+  assert!(matches!(
+    Variant::new(3, 3, 4, Rule::Freestyle),
+    Err(GameError::UnreachableRow(4, 3, 3))
+  ));
+  assert!(matches!(
+    Variant::new(9, 9, 4, Rule::Renju),
+    Err(GameError::RenjuRowTooShort)
+  ));
+}
+
+/// Places the marks of x and o in turns, x first.
+fn set_up(variant: Variant, x: &[&str], o: &[&str]) -> Game {
+  let mut game = Game::new(variant);
+
+  for (index, square) in x.iter().enumerate() {
+    play(&mut game, &[square]);
+
+    if let Some(square) = o.get(index) {
+      play(&mut game, &[square]);
+    }
+  }
+
+  game
+}
+
+/// Returns the outcome once the marks of x and o have been placed in turns, x first.
+fn get_outcome(variant: Variant, x: &[&str], o: &[&str]) -> Option<Outcome> {
+  set_up(variant, x, o).get_outcome().clone()
+}
+
+#[test]
+fn rows_of_k_win_in_every_direction() {
+  let variant = Variant::new(7, 6, 4, Rule::Freestyle).unwrap();
+  let o = ["a6", "c6", "e6"];
+
+  for x in [
+    ["a1", "b1", "c1", "d1"],
+    ["g2", "g3", "g4", "g5"],
+    ["a1", "b2", "c3", "d4"],
+    ["g1", "f2", "e3", "d4"],
+    ["d5", "c4", "b3", "a2"],
+  ] {
+    assert_eq!(get_outcome(variant, &x, &o), Some(Outcome::XWin), "{:?}", x);
+  }
+}
+
+#[test]
+fn rows_do_not_wrap_around_the_edges_of_the_board() {
+  let variant = Variant::new(7, 6, 4, Rule::Freestyle).unwrap();
+  let o = ["b6", "d6", "f6"];
+
+  for x in [
+    ["e1", "f1", "g1", "a2"],
+    ["e1", "f2", "g3", "a5"],
+    ["c1", "b2", "a3", "g3"],
+    ["a1", "b1", "c1", "e1"],
+  ] {
+    assert_eq!(get_outcome(variant, &x, &o), None, "{:?}", x);
+  }
+}
+
+#[test]
+fn the_widest_boards_are_won_along_their_last_column_and_row() {
+  let variant = Variant::new(Variant::MAXIMUM_WIDTH, 9, 5, Rule::Freestyle).unwrap();
+  let o = ["a1", "a2", "a3", "a4"];
+  assert_eq!(
+    get_outcome(variant, &["v9", "w9", "x9", "y9", "z9"], &o),
+    Some(Outcome::XWin)
+  );
+  assert_eq!(
+    get_outcome(variant, &["z5", "z6", "z7", "z8", "z9"], &o),
+    Some(Outcome::XWin)
+  );
+  assert_eq!(
+    get_outcome(variant, &["w8", "x8", "y8", "z8", "a9"], &o),
+    None
+  );
+  assert!(matches!(
+    Variant::new(Variant::MAXIMUM_WIDTH, 10, 5, Rule::Freestyle),
+    Err(GameError::UnsupportedBoard(26, 10))
+  ));
+}
+
+#[test]
+fn rows_longer_than_five_only_win_freestyle() {
+  let x = ["a1", "b1", "c1", "d1", "f1", "e1"];
+  let o = ["a15", "c15", "e15", "g15", "i15"];
+  let gomoku = |rule| {
+    let variant = Variant::GOMOKU;
+    Variant::new(
+      variant.get_width(),
+      variant.get_height(),
+      variant.get_k(),
+      rule,
+    )
+    .unwrap()
+  };
+
+  assert_eq!(
+    get_outcome(gomoku(Rule::Freestyle), &x, &o),
+    Some(Outcome::XWin)
+  );
+  assert_eq!(get_outcome(gomoku(Rule::Exact), &x, &o), None);
+  assert_eq!(
+    get_outcome(
+      gomoku(Rule::Exact),
+      &["a1", "b1", "c1", "d1", "e1"],
+      &o[..4]
+    ),
+    Some(Outcome::XWin)
+  );
+
+  let mut game = set_up(gomoku(Rule::Renju), &x[..5], &o);
+  assert!(matches!(
+    game.place_mark(&"e1".parse().unwrap()),
+    Err(GameError::SquareIsForbidden(_))
+  ));
+}
+
+#[test]
+fn renju_lets_o_win_with_longer_rows() {
+  let variant = Variant::new(15, 15, 5, Rule::Renju).unwrap();
+  assert_eq!(
+    get_outcome(
+      variant,
+      &["a15", "c15", "e15", "g15", "i15", "k15"],
+      &["a1", "b1", "c1", "d1", "f1", "e1"],
+    ),
+    Some(Outcome::OWin)
+  );
+}
+
+#[test]
+fn renju_forbids_x_two_open_threes_or_two_fours() {
+  let variant = Variant::new(15, 15, 5, Rule::Renju).unwrap();
+  let center: Square = "h8".parse().unwrap();
+
+  for (x, o) in [
+    (&["f8", "g8", "h6", "h7"][..], &["a1", "c1", "e1", "g1"][..]),
+    (
+      &["e8", "f8", "g8", "h5", "h6", "h7"],
+      &["a1", "c1", "e1", "g1", "i1", "k1"],
+    ),
+  ] {
+    let mut game = set_up(variant, x, o);
+    assert!(!game.get_empty_squares().contains(&center), "{:?}", x);
+    assert!(matches!(
+      game.place_mark(&center),
+      Err(GameError::SquareIsForbidden(_))
+    ));
+  }
+
+  let mut game = set_up(
+    variant,
+    &["f8", "g8", "h6", "h7"],
+    &["a1", "c1", "e1", "i8"],
+  );
+  assert!(
+    game.place_mark(&center).is_ok(),
+    "a blocked three is no threat"
+  );
+}
+
+#[test]
+fn taking_back_every_mark_returns_to_the_start() {
+  let start: Game = "x__/___/___ o".parse().unwrap();
+  let mut game = start.clone();
+  play(&mut game, &["b2", "c3", "b3", "b1", "c2", "a2", "a1", "c1"]);
+  let end = game.clone();
+  assert_eq!(*end.get_outcome(), Some(Outcome::Draw));
+
+  while game.undo_mark().is_ok() {}
+  assert!(game == start);
+  assert_eq!(*game.get_side_to_move(), Side::O);
+  assert_eq!(game.get_undone().len(), 8);
+  assert!(matches!(game.undo_mark(), Err(GameError::NothingToUndo)));
+
+  assert_eq!(game.redo_mark().unwrap(), Square::new(1, 1));
+  assert_eq!(*game.get_side_to_move(), Side::X);
+  while game.redo_mark().is_ok() {}
+  assert!(game == end);
+  assert_eq!(*game.get_outcome(), Some(Outcome::Draw));
+}
+
+#[test]
+fn malformed_positions_are_not_read() {
+  for text in [
+    "",
+    "xo_/_x_/__o",
+    "xo_/_x_/__o z",
+    "xo_/_x/__o x",
+    "xo_/_x_/__q o",
+    "xo_/_x_/__o o three",
+    "xo_/_x_/__o o 3 gomoku",
+    "xo_/_x_/__o o 3 freestyle extra",
+  ] {
+    assert!(
+      matches!(text.parse::<Game>(), Err(GameError::InvalidPosition(_))),
+      "{}",
+      text
+    );
+  }
+
+  assert!(matches!(
+    "xo_/_x_/__o o 4".parse::<Game>(),
+    Err(GameError::UnreachableRow(4, 3, 3))
+  ));
+}
+
+#[test]
+fn positions_tell_their_outcome_and_keep_their_variant() {
+  let game: Game = "xxx/oo_/___ o".parse().unwrap();
+  assert_eq!(*game.get_outcome(), Some(Outcome::XWin));
+  assert_eq!(*game.get_variant(), Variant::TIC_TAC_TOE);
+  assert_eq!(game.to_string(), "xxx/oo_/___ o");
+
+  let game: Game = "_______/___x___/___o___/_______/_______/_______/_______ x 5 renju"
+    .parse()
+    .unwrap();
+  assert_eq!(
+    *game.get_variant(),
+    Variant::new(7, 7, 5, Rule::Renju).unwrap()
+  );
+  assert_eq!(game.get_mark(&"d6".parse().unwrap()), Some(Side::X));
+  assert_eq!(game.get_mark(&"d5".parse().unwrap()), Some(Side::O));
+  assert_eq!(
+    game.to_string().parse::<Game>().unwrap().to_string(),
+    game.to_string()
+  );
+}
@@ -0,0 +1,140 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+  io::{BufRead, BufReader, Write},
+  net::{SocketAddr, TcpListener, TcpStream},
+  thread::{self, JoinHandle},
+};
+use tic_tac_toe::{
+  controller::Controller,
+  engine,
+  game::{Game, Square, Variant},
+  player::{Action, Clock, Minimax, Player, PlayerError, Random, Remote},
+};
+
+/// Gives up every game at its first move.
+struct Resigner;
+
+impl Player for Resigner {
+  fn get_name(&self) -> &str {
+    "resigner"
+  }
+
+  fn get_move(&mut self, _game: &Game, _clock: Option<&Clock>) -> Result<Square, PlayerError> {
+    unreachable!("resigners never move")
+  }
+
+  fn get_action(&mut self, _game: &Game, _clock: Option<&Clock>) -> Result<Action, PlayerError> {
+    Ok(Action::Resign)
+  }
+}
+
+/// Listens on a port of the loopback interface chosen by the system.
+fn listen() -> (TcpListener, SocketAddr) {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let address = listener.local_addr().unwrap();
+  (listener, address)
+}
+
+/// Joins the game hosted at `address` with a player made by `create`.
+fn join(address: SocketAddr, create: fn() -> Box<dyn Player>) -> JoinHandle<()> {
+  thread::spawn(move || engine::join(create(), address).unwrap())
+}
+
+/// Joins the game hosted at `address`, introduces itself and leaves.
+fn leave(address: SocketAddr) -> JoinHandle<()> {
+  thread::spawn(move || {
+    let stream = TcpStream::connect(address).unwrap();
+    let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), "tictactoe");
+    (&stream).write_all(b"id name leaver\nok\n").unwrap();
+  })
+}
+
+#[test]
+fn remote_engines_never_lose_to_random() {
+  let (listener, address) = listen();
+  let joined = join(address, || Box::new(Minimax::new()));
+  let remote = Remote::new(&listener).unwrap();
+  assert_eq!(remote.get_name(), "minimax");
+
+  let results = Controller::new(
+    Box::new(remote),
+    Box::new(Random::new()),
+    6,
+    true,
+    Game::new(Variant::TIC_TAC_TOE),
+    1,
+  )
+  .play_match()
+  .unwrap();
+  assert_eq!(results[1].get_wins(), 0);
+  joined.join().unwrap();
+}
+
+#[test]
+fn remote_players_that_resign_lose() {
+  let (listener, address) = listen();
+  let joined = join(address, || Box::new(Resigner));
+
+  let results = Controller::new(
+    Box::new(Remote::new(&listener).unwrap()),
+    Box::new(Random::new()),
+    2,
+    true,
+    Game::new(Variant::TIC_TAC_TOE),
+    1,
+  )
+  .play_match()
+  .unwrap();
+  assert_eq!(results[1].get_wins_as_x(), 1);
+  assert_eq!(results[1].get_wins_as_o(), 1);
+  joined.join().unwrap();
+}
+
+#[test]
+fn hosts_notice_remote_players_leaving() {
+  let (listener, address) = listen();
+  let joined = leave(address);
+  let mut remote = Remote::new(&listener).unwrap();
+  joined.join().unwrap();
+  assert_eq!(remote.get_name(), "leaver");
+
+  let error = remote
+    .get_move(&Game::new(Variant::TIC_TAC_TOE), None)
+    .unwrap_err();
+  assert!(error.is_disconnected(), "{}", error);
+}
+
+#[test]
+fn matches_end_when_a_remote_player_leaves() {
+  let (listener, address) = listen();
+  let joined = leave(address);
+  let remote = Remote::new(&listener).unwrap();
+  joined.join().unwrap();
+
+  let results = Controller::new(
+    Box::new(Random::new()),
+    Box::new(remote),
+    5,
+    false,
+    Game::new(Variant::TIC_TAC_TOE),
+    1,
+  )
+  .play_match()
+  .unwrap();
+  assert_eq!(results[0].get_wins_as_x(), 1);
+  assert_eq!(results[1].get_wins() + results[1].get_draws(), 0);
+}
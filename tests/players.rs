@@ -0,0 +1,328 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{Duration, Instant};
+use tic_tac_toe::{
+  controller::{Controller, GameCount},
+  game::{Game, Rule, Square, Variant},
+  player::{
+    AlphaBeta, Context, Mcts, MctsConfig, MctsError, Minimax, Parallelization, Perfect, Player,
+    PlayerError, PlayerSpec, Random,
+  },
+};
+
+const GAMES: GameCount = 20;
+
+/// Returns the default search settings of 'mcts' players with `rounds` rounds.
+fn get_config(rounds: u32) -> MctsConfig {
+  MctsConfig::new(
+    rounds,
+    MctsConfig::EXPLORATION_DEFAULT,
+    None,
+    MctsConfig::NODES_CAPACITY_DEFAULT,
+  )
+}
+
+/// Plays `player` against random moves, half of the games as x, and returns the games it lost.
+fn count_losses(player: Box<dyn Player>, seed: u64) -> GameCount {
+  let results = Controller::new(
+    player,
+    Box::new(Random::new()),
+    GAMES,
+    true,
+    Game::new(Variant::TIC_TAC_TOE),
+    seed,
+  )
+  .play_match()
+  .unwrap();
+  results[1].get_wins()
+}
+
+#[test]
+fn perfect_players_never_lose_to_random_moves() {
+  assert_eq!(count_losses(Box::new(Minimax::new()), 1), 0);
+  assert_eq!(count_losses(Box::new(AlphaBeta::new()), 2), 0);
+  assert_eq!(count_losses(Box::new(Perfect::new(None)), 3), 0);
+}
+
+#[test]
+fn rave_searches_never_lose_to_random_moves() {
+  let config = get_config(1000).with_rave(Some(50.0));
+  assert_eq!(count_losses(Box::new(Mcts::new(config)), 13), 0);
+}
+
+#[test]
+fn perfect_players_draw_against_each_other() {
+  let results = Controller::new(
+    Box::new(Minimax::new()),
+    Box::new(Perfect::new(None)),
+    4,
+    true,
+    Game::new(Variant::TIC_TAC_TOE),
+    4,
+  )
+  .play_match()
+  .unwrap();
+  assert_eq!(results[0].get_draws(), 4);
+}
+
+#[test]
+fn alpha_beta_plays_the_moves_minimax_rates_best() {
+  let mut alpha_beta = AlphaBeta::new();
+  let mut minimax = Minimax::new();
+  let mut random = Random::new();
+
+  for seed in 0..10 {
+    let mut game = Game::new(Variant::TIC_TAC_TOE);
+    random.new_game(seed);
+
+    while game.get_outcome().is_none() {
+      let evaluations = minimax.evaluate(&game).unwrap();
+      let rank = |square: &Square| {
+        evaluations
+          .iter()
+          .find(|(other, _)| other == square)
+          .map(|(_, evaluation)| evaluation.get_rank())
+      };
+      let best = evaluations
+        .iter()
+        .map(|(_, evaluation)| evaluation.get_rank())
+        .max();
+      let square = alpha_beta.get_move(&game, None).unwrap();
+      assert_eq!(rank(&square), best, "{} in {}", square, game);
+
+      game
+        .place_mark(&random.get_move(&game, None).unwrap())
+        .unwrap();
+    }
+  }
+}
+
+#[test]
+fn alpha_beta_visits_fewer_nodes_than_minimax() {
+  let game = Game::new(Variant::TIC_TAC_TOE);
+  let mut alpha_beta = AlphaBeta::new();
+  let mut minimax = Minimax::new();
+  alpha_beta.get_move(&game, None).unwrap();
+  minimax.get_move(&game, None).unwrap();
+  assert!(alpha_beta.get_visited_nodes().unwrap() < minimax.get_visited_nodes().unwrap());
+}
+
+#[test]
+fn mcts_takes_an_immediate_win() {
+  let config = get_config(1000);
+  let mut mcts = Mcts::new(config);
+  mcts.new_game(5);
+  let game: Game = "xx_/oo_/___ x".parse().unwrap();
+  assert_eq!(mcts.get_move(&game, None).unwrap(), Square::new(2, 2));
+}
+
+#[test]
+fn mcts_runs_the_rounds_it_is_given() {
+  let game = Game::new(Variant::new(5, 5, 4, Rule::Freestyle).unwrap());
+
+  for rounds in [1, 100, 1000] {
+    let mut mcts = Mcts::new(MctsConfig::new(
+      rounds,
+      MctsConfig::EXPLORATION_DEFAULT,
+      None,
+      16,
+    ));
+    mcts.new_game(6);
+    mcts.get_move(&game, None).unwrap();
+    assert_eq!(mcts.get_playouts(), Some(rounds.into()));
+  }
+}
+
+#[test]
+fn mcts_searches_until_its_time_limit_instead_of_its_rounds() {
+  let game = Game::new(Variant::new(5, 5, 4, Rule::Freestyle).unwrap());
+  let mut mcts = Mcts::new(MctsConfig::new(
+    1,
+    MctsConfig::EXPLORATION_DEFAULT,
+    Some(Duration::from_millis(100)),
+    MctsConfig::NODES_CAPACITY_DEFAULT,
+  ));
+  mcts.new_game(7);
+  let start = Instant::now();
+  mcts.get_move(&game, None).unwrap();
+  assert!(start.elapsed() >= Duration::from_millis(100));
+  assert!(mcts.get_playouts().unwrap() > 1);
+}
+
+#[test]
+fn mcts_specs_set_the_search_parameters() {
+  let context = Context::new(get_config(MctsConfig::ROUNDS_DEFAULT), None);
+  let mut player = "mcts:rounds=50,c=0.5,capacity=16"
+    .parse::<PlayerSpec>()
+    .unwrap()
+    .get_player(&context)
+    .unwrap();
+  player.new_game(8);
+  player
+    .get_move(
+      &Game::new(Variant::new(5, 5, 4, Rule::Freestyle).unwrap()),
+      None,
+    )
+    .unwrap();
+  assert_eq!(player.get_playouts(), Some(50));
+}
+
+#[test]
+fn mcts_searches_only_near_the_stones_with_a_neighborhood() {
+  let config = get_config(200).with_neighborhood(Some(2));
+  let mut mcts = Mcts::new(config);
+  let mut game = Game::new(Variant::GOMOKU);
+  let stone = Square::new(3, 11);
+  game.place_mark(&stone).unwrap();
+
+  let near: Vec<Square> = (1..=5)
+    .flat_map(|column| (9..=13).map(move |row| Square::new(column, row)))
+    .collect();
+
+  for seed in 0..5 {
+    mcts.new_game(seed);
+    let square = mcts.get_move(&game, None).unwrap();
+    assert!(near.contains(&square), "{}", square);
+  }
+}
+
+/// Plays a game of `player` against itself on an empty board of `variant`, seeded with `seed`,
+/// and returns its moves.
+fn play_itself(mut player: impl Player, variant: Variant, seed: u64) -> Vec<String> {
+  let mut game = Game::new(variant);
+  player.new_game(seed);
+
+  while game.get_outcome().is_none() {
+    let square = player.get_move(&game, None).unwrap();
+    game.place_mark(&square).unwrap();
+    player.notify_move(&square);
+  }
+
+  game.get_history().iter().map(Square::to_string).collect()
+}
+
+#[test]
+fn root_parallel_searches_replay_from_their_seed() {
+  let config = get_config(500).with_threads(4, Parallelization::Root);
+  let variant = Variant::new(4, 4, 4, Rule::Freestyle).unwrap();
+  let moves = play_itself(Mcts::new(config.clone()), variant, 3);
+  assert_eq!(play_itself(Mcts::new(config.clone()), variant, 3), moves);
+  assert_ne!(play_itself(Mcts::new(config), variant, 4), moves);
+}
+
+#[test]
+fn tree_parallel_searches_take_an_immediate_win_and_block_one() {
+  let config = get_config(1000).with_threads(4, Parallelization::Tree);
+  let mut mcts = Mcts::new(config);
+  let game: Game = "xx_/oo_/___ x".parse().unwrap();
+  assert_eq!(mcts.get_move(&game, None).unwrap(), Square::new(2, 2));
+
+  let game: Game = "oo_/x__/x__ x".parse().unwrap();
+  assert_eq!(mcts.get_move(&game, None).unwrap(), Square::new(2, 2));
+}
+
+/// Pins the games of seeded players, which change only when their search or the way they draw
+/// random numbers does.
+#[test]
+fn seeded_players_play_the_same_moves() {
+  let config = get_config(200);
+  let variant = Variant::new(4, 4, 4, Rule::Freestyle).unwrap();
+  assert_eq!(
+    play_itself(Random::new(), variant, 8),
+    ["a3", "d4", "c1", "b4", "c4", "a2", "d1", "c2", "b2", "d3", "b1", "c3", "a1"]
+  );
+  assert_eq!(
+    play_itself(Mcts::new(config), variant, 8),
+    [
+      "b3", "a1", "a3", "b2", "c2", "d3", "d4", "c1", "b4", "a4", "d1", "c4", "c3", "a2", "d2",
+      "b1"
+    ]
+  );
+}
+
+#[test]
+fn seeded_matches_refuse_tree_parallel_searches() {
+  let context = Context::new(get_config(MctsConfig::ROUNDS_DEFAULT), None).with_seeded(true);
+  let spec = |text: &str| text.parse::<PlayerSpec>().unwrap();
+
+  assert!(matches!(
+    spec("mcts:threads=4,parallelization=tree").get_player(&context),
+    Err(PlayerError::Mcts(MctsError::NotReproducible))
+  ));
+  assert!(spec("mcts:threads=1,parallelization=tree")
+    .get_player(&context)
+    .is_ok());
+  assert!(spec("mcts:threads=4,parallelization=root")
+    .get_player(&context)
+    .is_ok());
+  assert!(spec("mcts:threads=4,parallelization=tree")
+    .get_player(&context.with_seeded(false))
+    .is_ok());
+}
+
+#[test]
+fn mcts_analysis_gives_moves_proven_to_lose_no_wins() {
+  let config = get_config(1000);
+  let mut mcts = Mcts::new(config);
+  mcts.new_game(1);
+  let game: Game = "xo_/_o_/__x x".parse().unwrap();
+
+  for (square, _, mean) in mcts.analyze(&game).unwrap() {
+    match square.to_string().as_str() {
+      "b1" => assert_eq!(mean, 0.5),
+      _ => assert_eq!(mean, 0.0, "{} is not a loss", square),
+    }
+  }
+}
+
+#[test]
+fn player_specs_check_their_options() {
+  let context = Context::new(get_config(MctsConfig::ROUNDS_DEFAULT), None);
+  let spec: PlayerSpec = "mcts:rounds=100,parallelization=tree".parse().unwrap();
+  assert_eq!(spec.to_string(), "mcts:rounds=100,parallelization=tree");
+  assert!(!spec.is_interactive());
+  assert_eq!(
+    spec.get_player(&context).unwrap().get_name(),
+    "monte carlo tree search"
+  );
+  assert!("user".parse::<PlayerSpec>().unwrap().is_interactive());
+  assert!("mcts:c=0,rave=0".parse::<PlayerSpec>().is_ok());
+
+  for (text, expected) in [
+    ("chess", "UnknownPlayerType"),
+    ("mcts:rounds", "InvalidOption"),
+    ("mcts:depth=2", "UnknownOption"),
+    ("mcts:rounds=0", "InvalidOptionValue"),
+    ("mcts:parallelization=leaf", "InvalidOptionValue"),
+    ("mcts:c=nan", "InvalidOptionValue"),
+    ("mcts:c=-3", "InvalidOptionValue"),
+    ("mcts:c=inf", "InvalidOptionValue"),
+    ("mcts:rave=-5", "InvalidOptionValue"),
+    ("mcts:capacity=4294967295", "InvalidOptionValue"),
+    ("mcts:threads=65535", "InvalidOptionValue"),
+    ("minimax:rounds=1", "UnexpectedOptions"),
+  ] {
+    let error = text.parse::<PlayerSpec>().err().unwrap();
+    let name = match error {
+      PlayerError::UnknownPlayerType(..) => "UnknownPlayerType",
+      PlayerError::InvalidOption(..) => "InvalidOption",
+      PlayerError::UnknownOption(..) => "UnknownOption",
+      PlayerError::InvalidOptionValue(..) => "InvalidOptionValue",
+      PlayerError::UnexpectedOptions(..) => "UnexpectedOptions",
+      _ => "another error",
+    };
+    assert_eq!(name, expected, "{}", text);
+  }
+}
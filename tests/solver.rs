@@ -0,0 +1,100 @@
+// Copyright 2023 Kamil Gloc
+
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+
+//  http://www.apache.org/licenses/LICENSE-2.0
+
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use tic_tac_toe::{
+  game::{Game, Rule, Variant},
+  player::{Evaluation, Minimax, Player},
+  solver::{SolverError, Table},
+};
+
+/// Checks the move minimax picks in every position reachable from `game` against the best moves
+/// of the solved table.
+fn check_moves(
+  minimax: &mut Minimax,
+  table: &Table,
+  game: &mut Game,
+  checked: &mut HashSet<String>,
+) {
+  if game.get_outcome().is_some() || !checked.insert(game.to_string()) {
+    return;
+  }
+
+  let square = minimax.get_move(game, None).unwrap();
+  let best = table.get(game).unwrap().get_best_squares(game);
+  assert!(
+    best.contains(&square),
+    "{} is not a best move in {}",
+    square,
+    game
+  );
+
+  for square in game.get_empty_squares() {
+    game.place_mark(&square).unwrap();
+    check_moves(minimax, table, game, checked);
+    game.undo_mark().unwrap();
+  }
+}
+
+#[test]
+fn solver_reaches_every_position() {
+  let table = Table::solve(Variant::TIC_TAC_TOE).unwrap();
+  assert_eq!(table.get_positions_count(), 5478);
+  let game = Game::new(Variant::TIC_TAC_TOE);
+  assert!(table.get(&game).unwrap().get_evaluation() == Evaluation::Draw);
+}
+
+#[test]
+fn minimax_picks_a_best_move() {
+  for variant in [
+    Variant::TIC_TAC_TOE,
+    Variant::new(4, 3, 3, Rule::Freestyle).unwrap(),
+  ] {
+    let table = Table::solve(variant).unwrap();
+    let mut checked = HashSet::new();
+    check_moves(
+      &mut Minimax::new(),
+      &table,
+      &mut Game::new(variant),
+      &mut checked,
+    );
+  }
+}
+
+#[test]
+fn tables_are_read_back_as_saved() {
+  let variant = Variant::new(3, 3, 3, Rule::Exact).unwrap();
+  let table = Table::solve(variant).unwrap();
+  let path = std::env::temp_dir().join(format!("tic-tac-toe-{}.table", std::process::id()));
+  table.save(&path).unwrap();
+  let loaded = Table::load(&path);
+  std::fs::remove_file(&path).unwrap();
+  let loaded = loaded.unwrap();
+
+  assert_eq!(loaded.get_variant(), &variant);
+  assert_eq!(loaded.get_positions_count(), table.get_positions_count());
+  let game: Game = "x__/_o_/___ x 3 exact".parse().unwrap();
+  assert_eq!(
+    loaded.get(&game).unwrap().get_best_squares(&game),
+    table.get(&game).unwrap().get_best_squares(&game)
+  );
+}
+
+#[test]
+fn boards_with_too_many_squares_are_not_solved() {
+  assert!(matches!(
+    Table::solve(Variant::new(5, 4, 4, Rule::Freestyle).unwrap()),
+    Err(SolverError::BoardTooLarge)
+  ));
+}